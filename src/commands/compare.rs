@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+
+use crate::{stats, Run, RunSelection, TestResult};
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+/// percentiles reported for each comparison, confidence intervals are calculated for p50 and p99
+static PERCENTILES: [(&str, f64); 5] = [
+    ("P50", 0.5),
+    ("P75", 0.75),
+    ("P95", 0.95),
+    ("P99", 0.99),
+    ("P99.9", 0.999),
+];
+
+#[derive(Parser, Debug)]
+pub(crate) struct Options {
+    /// the baseline: a test name, a run (id, label or `latest`) or `<run>:<test>`
+    a: String,
+
+    /// compared against the baseline, in the same form as `a`
+    b: String,
+
+    /// significance level for flagging regressions
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// number of bootstrap resamples used for confidence intervals
+    #[arg(long, default_value_t = 1000)]
+    iterations: usize,
+
+    /// run to load test names from when `a` or `b` is not a run
+    #[command(flatten)]
+    run: RunSelection,
+}
+
+enum Target {
+    Run(Run),
+    Test { dir: PathBuf, name: String },
+}
+
+impl Target {
+    fn parse(value: &str, data_dir: &Path, run: &RunSelection) -> Result<Self> {
+        if let Some((run, name)) = value.split_once(':') {
+            let run =
+                Run::find(data_dir, run).ok_or_else(|| anyhow!("no run found matching '{run}'"))?;
+            return Ok(Self::Test {
+                dir: run.dir,
+                name: name.to_owned(),
+            });
+        }
+        match Run::find(data_dir, value) {
+            Some(run) => Ok(Self::Run(run)),
+            None => Ok(Self::Test {
+                dir: run.dir(data_dir)?,
+                name: value.to_owned(),
+            }),
+        }
+    }
+}
+
+pub(crate) fn compare(
+    Options {
+        a,
+        b,
+        alpha,
+        iterations,
+        run,
+    }: Options,
+    names: Option<Vec<String>>,
+    data_dir: PathBuf,
+) -> Result<()> {
+    let a = Target::parse(&a, &data_dir, &run)?;
+    let b = Target::parse(&b, &data_dir, &run)?;
+
+    let pairs: Vec<(TestResult, TestResult)> = match (a, b) {
+        (
+            Target::Test { dir, name },
+            Target::Test {
+                dir: b_dir,
+                name: b_name,
+            },
+        ) => {
+            vec![(load(&dir, &name)?, load(&b_dir, &b_name)?)]
+        }
+        (Target::Run(a), Target::Run(b)) => {
+            let pairs: Vec<_> = a
+                .manifest
+                .tests
+                .iter()
+                .filter(|t| b.manifest.tests.contains(t))
+                .filter(|t| names.as_ref().is_none_or(|n| n.contains(t)))
+                .filter_map(|t| Some((load(&a.dir, t).ok()?, load(&b.dir, t).ok()?)))
+                .collect();
+            if pairs.is_empty() {
+                return Err(anyhow!(
+                    "runs '{}' and '{}' have no tests in common",
+                    a.manifest.id,
+                    b.manifest.id
+                ));
+            }
+            pairs
+        }
+        _ => return Err(anyhow!("unable to compare a run with a single test")),
+    };
+
+    for (a, b) in pairs {
+        report(&a, &b, alpha, iterations);
+    }
+
+    Ok(())
+}
+
+fn load(dir: &Path, name: &str) -> Result<TestResult> {
+    TestResult::load_filtered(dir, Some(vec![name.to_owned()]))?
+        .next()
+        .ok_or_else(|| anyhow!("no results for '{name}' in {}", dir.display()))
+}
+
+fn report(a: &TestResult, b: &TestResult, alpha: f64, iterations: usize) {
+    let latencies = |result: &TestResult| {
+        let mut latencies: Vec<_> = result
+            .success_responses()
+            .map(|r| r.time.as_secs_f64() * 1000f64)
+            .collect();
+        latencies.sort_unstable_by(f64::total_cmp);
+        latencies
+    };
+    let (a_ms, b_ms) = (latencies(a), latencies(b));
+    let (a_total, b_total) = (a.responses.len(), b.responses.len());
+    let (a_success, b_success) = (a_ms.len(), b_ms.len());
+
+    let mut regressions = vec![];
+    println!("{} -> {}:", a.name, b.name);
+
+    let rate = |success: usize, total: usize| 100f64 * success as f64 / total.max(1) as f64;
+    let (a_rate, b_rate) = (rate(a_success, a_total), rate(b_success, b_total));
+    let rate_p = stats::two_proportion_p(a_success, a_total, b_success, b_total);
+    let verdict = match rate_p {
+        Some(p) if p < alpha && b_rate < a_rate => {
+            regressions.push("success rate");
+            " regression"
+        }
+        Some(p) if p < alpha => " improvement",
+        _ => "",
+    };
+    println!(
+        "  success rate: {a_rate:.2}% -> {b_rate:.2}% ({:+.2}pp, p={}){verdict}",
+        b_rate - a_rate,
+        format_p(rate_p)
+    );
+
+    let intervals =
+        stats::bootstrap_percentile_diff(&a_ms, &b_ms, &[0.5, 0.99], iterations, 1f64 - alpha);
+    for (label, ratio) in PERCENTILES {
+        let (Some(a_p), Some(b_p)) = (
+            stats::percentile(&a_ms, ratio),
+            stats::percentile(&b_ms, ratio),
+        ) else {
+            println!("  {label}: unable to calculate");
+            continue;
+        };
+        let diff = b_p - a_p;
+        // a zero baseline has no relative change
+        let relative = match a_p > 0f64 {
+            true => format!("{:+.1}%", 100f64 * diff / a_p),
+            false => "n/a".into(),
+        };
+        let mut line = format!("  {label}: {a_p:.2}ms -> {b_p:.2}ms ({diff:+.2}ms, {relative})");
+
+        let interval = match (label, &intervals) {
+            ("P50", Some(i)) => Some(i[0]),
+            ("P99", Some(i)) => Some(i[1]),
+            _ => None,
+        };
+        if let Some((low, high)) = interval {
+            line += &format!(
+                " {:.0}% CI [{low:+.2}ms, {high:+.2}ms]",
+                100f64 * (1f64 - alpha)
+            );
+            if low > 0f64 {
+                regressions.push(label);
+                line += " regression";
+            } else if high < 0f64 {
+                line += " improvement";
+            }
+        }
+        println!("{line}");
+    }
+
+    // a negative z means the first test's latencies rank lower, so the second is slower
+    match stats::mann_whitney_u(&a_ms, &b_ms) {
+        Some(mw) => {
+            let verdict = match mw.p < alpha {
+                true if mw.z < 0f64 => {
+                    regressions.push("latency distribution");
+                    " regression"
+                }
+                true => " improvement",
+                false => "",
+            };
+            println!(
+                "  latency distribution (Mann-Whitney U): U={:.0}, z={:.2}, p={}{verdict}",
+                mw.u,
+                mw.z,
+                format_p(Some(mw.p)),
+            );
+        }
+        None => println!("  latency distribution (Mann-Whitney U): unable to calculate"),
+    }
+
+    match regressions.is_empty() {
+        true => println!("  no significant regressions"),
+        false => println!("  REGRESSION: {}", regressions.join(", ")),
+    }
+    println!();
+}
+
+fn format_p(p: Option<f64>) -> String {
+    match p {
+        Some(p) if p < 0.0001 => "<0.0001".into(),
+        Some(p) => format!("{p:.4}"),
+        None => "n/a".into(),
+    }
+}
@@ -1,8 +1,12 @@
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
-use crate::{ResponseInfo, TestResult};
+use crate::{
+    response_info::{Status, Timing},
+    stats, ResponseInfo, RunSelection, TestResult,
+};
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use regex::Regex;
 
 #[derive(Subcommand, Debug)]
 pub(crate) enum Command {
@@ -19,25 +23,71 @@ pub(crate) enum Command {
         #[arg(long, short, default_value_t = 1)]
         count: u64,
     },
-    Percentiles,
+    Percentiles {
+        /// measure latency from when each request was meant to be sent, correcting for coordinated omission
+        #[arg(long)]
+        corrected: bool,
+
+        /// the percentiles to report, eg. `50,90,99`
+        #[arg(
+            long,
+            use_value_delimiter = true,
+            default_values_t = [75.0, 95.0, 99.0, 99.5, 99.9]
+        )]
+        percentiles: Vec<f64>,
+
+        /// include failed requests, which often fail fast or time out
+        #[arg(long)]
+        include_failures: bool,
+
+        /// the latency to report
+        #[arg(long, value_enum, default_value = "total")]
+        metric: Metric,
+    },
     Failures {
         /// number of requests to find
         #[arg(long, short, default_value_t = 1)]
         count: usize,
+
+        /// group the failures by reason and status code instead of listing them
+        #[arg(long)]
+        summary: bool,
+
+        /// collected headers whose values also split the groups of the summary
+        #[arg(long)]
+        group_by: Vec<String>,
+
+        /// number of requests to show the collected values of for each group of the summary
+        #[arg(long, default_value_t = 3)]
+        samples: usize,
     },
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Metric {
+    /// the time the request took
+    Total,
+    /// the time the server reported in the test's `latency_header`
+    Server,
+    /// the total minus the server latency, the time spent in the network and infrastructure
+    Infrastructure,
+}
+
 #[derive(Parser, Debug)]
 pub(crate) struct Options {
     #[command(subcommand)]
     command: Command,
+
+    #[command(flatten)]
+    run: RunSelection,
 }
 
 pub(crate) fn extract(
-    Options { command }: Options,
+    Options { command, run }: Options,
     names: Option<Vec<String>>,
     data_dir: PathBuf,
 ) -> Result<()> {
+    let data_dir = run.dir(&data_dir)?;
     match command {
         Command::Range {
             min_ms,
@@ -68,22 +118,108 @@ pub(crate) fn extract(
                 println!();
             }
         }
-        Command::Percentiles => {
-            for mut result in TestResult::load_filtered(&data_dir, names)? {
-                result.responses.sort_unstable_by_key(|r| r.time);
-                println!("{}:", result.name);
-                println!("  P75: {}", percentile_time(&result.responses, 0.75));
-                println!("  P95: {}", percentile_time(&result.responses, 0.95));
-                println!("  P99: {}", percentile_time(&result.responses, 0.99));
-                println!("  P99.5: {}", percentile_time(&result.responses, 0.995));
-                println!("  P99.9: {}", percentile_time(&result.responses, 0.999));
+        Command::Percentiles {
+            corrected,
+            percentiles,
+            include_failures,
+            metric,
+        } => {
+            if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+                return Err(anyhow!("Percentile {p} isn't between 0 and 100"));
+            }
+            if corrected && metric == Metric::Server {
+                return Err(anyhow!(
+                    "Server latency can't be corrected for coordinated omission"
+                ));
+            }
+            let time = |r: &ResponseInfo| match corrected {
+                true => r.corrected_time(),
+                false => r.time,
+            };
+            let value = |r: &ResponseInfo| match metric {
+                Metric::Total => Some(time(r)),
+                Metric::Server => r.server_latency,
+                Metric::Infrastructure => Some(time(r).saturating_sub(r.server_latency?)),
+            };
+
+            let mut notes = vec![];
+            match metric {
+                Metric::Total => {}
+                Metric::Server => notes.push("server latency"),
+                Metric::Infrastructure => notes.push("infrastructure latency"),
+            }
+            if corrected {
+                notes.push("corrected for coordinated omission");
+            }
+            if include_failures {
+                notes.push("including failures");
+            }
+
+            for result in TestResult::load_filtered(&data_dir, names)? {
+                match notes.is_empty() {
+                    true => println!("{}:", result.name),
+                    false => println!("{} ({}):", result.name, notes.join(", ")),
+                }
+                let responses: Vec<_> = result
+                    .responses
+                    .iter()
+                    .filter(|r| include_failures || r.status.is_success())
+                    .collect();
+                print_stats(
+                    responses.iter().filter_map(|r| value(r)),
+                    &percentiles,
+                    "  ",
+                );
+
+                // the phases and steps break down the total
+                if metric == Metric::Total {
+                    // including the download, for the responses whose body was read
+                    let totals: Vec<_> = responses
+                        .iter()
+                        .filter_map(|r| Some(time(r) + r.timing?.download?))
+                        .collect();
+                    if !totals.is_empty() {
+                        println!("  with body ({}/{}):", totals.len(), responses.len());
+                        print_stats(totals.into_iter(), &percentiles, "    ");
+                    }
+                    print_phases(&responses, &percentiles);
+                }
+
+                // break down a weighted mix by request
+                for name in result.request_names() {
+                    println!("  {name}:");
+                    let requests = responses
+                        .iter()
+                        .filter(|r| r.request_name.as_deref() == Some(name));
+                    print_stats(requests.filter_map(|r| value(r)), &percentiles, "    ");
+                }
+                // and a scenario by step
+                if metric == Metric::Total {
+                    for name in result.step_names() {
+                        println!("  step {name}:");
+                        let steps = responses.iter().flat_map(|r| &r.steps);
+                        let times = steps.filter(|s| s.name == name).map(|s| s.time);
+                        print_stats(times, &percentiles, "    ");
+                    }
+                }
             }
         }
-        Command::Failures { count } => {
+        Command::Failures {
+            count,
+            summary,
+            group_by,
+            samples,
+        } => {
             if count == 0 {
                 return Err(anyhow!("Count cannot be 0"));
             }
             for result in TestResult::load_filtered(&data_dir, names)? {
+                if summary {
+                    print_failure_summary(&result, &group_by, samples);
+                    print_schema_violations(&result);
+                    println!();
+                    continue;
+                }
                 let responses: Vec<_> = result.failure_responses().take(count).collect();
 
                 println!("{}: (found {}/{})", result.name, responses.len(), count);
@@ -94,6 +230,21 @@ pub(crate) fn extract(
                 } else {
                     println!("No failed requests")
                 }
+                print_counts(
+                    "failures by kind",
+                    result
+                        .failure_responses()
+                        .filter_map(|r| r.status.failure_kind())
+                        .map(|kind| kind.to_string()),
+                );
+                print_counts(
+                    "failures by status code",
+                    result.failure_responses().map(|r| match r.status_code {
+                        Some(code) => code.to_string(),
+                        None => "no response".into(),
+                    }),
+                );
+                print_schema_violations(&result);
                 println!();
             }
         }
@@ -102,14 +253,214 @@ pub(crate) fn extract(
     Ok(())
 }
 
-fn percentile_time(responses: &[ResponseInfo], ratio: f64) -> String {
-    let n_total = responses.len();
-    let n = (n_total as f64 * ratio).floor() as usize;
-    let subset = &responses[n..];
-    let count = n_total - n;
+/// the reason, status code and `group_by` collected values shared by a group of failures
+type FailureGroup = (String, Option<u16>, Vec<String>);
+
+/// group the failures by kind, normalized reason, status code and the `group_by` collected values,
+/// most common first
+fn print_failure_summary(result: &TestResult, group_by: &[String], samples: usize) {
+    let failures: Vec<_> = result.failure_responses().collect();
+    println!(
+        "{}: ({} failures of {} requests)",
+        result.name,
+        failures.len(),
+        result.responses.len()
+    );
+    if failures.is_empty() {
+        println!("No failed requests");
+        return;
+    }
+
+    let normalize = ReasonNormalizer::new();
+    let mut groups: HashMap<FailureGroup, Vec<&ResponseInfo>> = HashMap::new();
+    for response in &failures {
+        let Status::Failure { kind, reason } = &response.status else {
+            continue;
+        };
+        let values = group_by
+            .iter()
+            .map(|header| match response.collected.get(header) {
+                Some(value) if !value.is_empty() => format!("{header}: {value}"),
+                _ => format!("{header}: none"),
+            })
+            .collect();
+        let key = (
+            format!("{kind}: {}", normalize.apply(reason)),
+            response.status_code,
+            values,
+        );
+        groups.entry(key).or_default().push(response);
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|(a, a_responses), (b, b_responses)| {
+        (b_responses.len().cmp(&a_responses.len())).then_with(|| a.cmp(b))
+    });
+    for ((reason, status_code, values), responses) in groups {
+        let percent = 100.0 * responses.len() as f64 / failures.len() as f64;
+        println!("  {} ({percent:.1}%) {reason}", responses.len());
+
+        let status = match status_code {
+            Some(code) => format!("status: {code}"),
+            None => "status: no response".into(),
+        };
+        println!("    {}", [vec![status], values].concat().join(", "));
+
+        let mut times: Vec<_> = responses.iter().map(|r| r.time).collect();
+        times.sort_unstable();
+        let mean = times.iter().sum::<Duration>() / times.len() as u32;
+        let at = |ratio| stats::percentile(&times, ratio).unwrap_or_default();
+        println!(
+            "    latency: min {:?}, mean {mean:?}, p50 {:?}, p99 {:?}, max {:?}",
+            times[0],
+            at(0.5),
+            at(0.99),
+            times[times.len() - 1]
+        );
+
+        // eg. the request ids to look the failures up in the service's logs
+        let examples: Vec<_> = responses
+            .iter()
+            .map(|r| {
+                let mut collected: Vec<_> = r
+                    .collected
+                    .iter()
+                    .filter(|(header, value)| !value.is_empty() && !group_by.contains(header))
+                    .map(|(header, value)| format!("{header}={value}"))
+                    .collect();
+                collected.sort();
+                collected.join(" ")
+            })
+            .filter(|collected| !collected.is_empty())
+            .take(samples)
+            .collect();
+        if !examples.is_empty() {
+            println!("    eg. {}", examples.join(", "));
+        }
+    }
+}
+
+/// replaces the parts of a failure reason that differ between requests, so alike failures group together
+struct ReasonNormalizer([(Regex, &'static str); 3]);
+
+impl ReasonNormalizer {
+    fn new() -> Self {
+        let regex = |pattern| Regex::new(pattern).expect("invalid normalizer pattern");
+        Self([
+            (regex(r"https?://[^\s)]+"), "<url>"),
+            (
+                regex(
+                    r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+                ),
+                "<uuid>",
+            ),
+            (regex(r"\d+(\.\d+)?"), "#"),
+        ])
+    }
+
+    fn apply(&self, reason: &str) -> String {
+        let mut reason = reason.to_owned();
+        for (pattern, replacement) in &self.0 {
+            reason = pattern.replace_all(&reason, *replacement).into_owned();
+        }
+        reason
+    }
+}
+
+/// count each value, most common first
+fn print_counts(title: &str, values: impl Iterator<Item = String>) {
+    let mut counts: Vec<(String, usize)> = vec![];
+    for value in values {
+        match counts.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return;
+    }
+
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    println!("{title}:");
+    for (value, count) in counts {
+        let percent = 100.0 * count as f64 / total as f64;
+        println!("  {value}: {count} ({percent:.1}%)");
+    }
+}
+
+/// count the schema violations at each JSON pointer, most common first
+fn print_schema_violations(result: &TestResult) {
+    let mut pointers: Vec<(&str, usize, &str)> = vec![];
+    for violation in result.responses.iter().flat_map(|r| &r.violations) {
+        match pointers
+            .iter_mut()
+            .find(|(p, ..)| *p == violation.location())
+        {
+            Some((_, count, _)) => *count += 1,
+            None => pointers.push((violation.location(), 1, &violation.message)),
+        }
+    }
+    if pointers.is_empty() {
+        return;
+    }
+
+    pointers.sort_by_key(|(_, count, _)| std::cmp::Reverse(*count));
+    println!("schema violations:");
+    for (pointer, count, message) in pointers {
+        println!("  {pointer}: {count} (eg. {message})");
+    }
+}
+
+/// how many requests reused a connection, and the percentiles of each phase of the requests
+fn print_phases(responses: &[&ResponseInfo], percentiles: &[f64]) {
+    let timings: Vec<_> = responses.iter().filter_map(|r| r.timing).collect();
+    if timings.is_empty() {
+        return;
+    }
+    let reused: Vec<_> = timings.iter().filter_map(Timing::reused).collect();
+    if !reused.is_empty() {
+        let count = reused.iter().filter(|r| **r).count();
+        println!("  reused connections: {count}/{}", reused.len());
+    }
+    for (i, name) in Timing::PHASES.iter().enumerate() {
+        let times: Vec<_> = timings.iter().filter_map(|t| t.phases()[i]).collect();
+        if !times.is_empty() {
+            println!("  {name}:");
+            print_stats(times.into_iter(), percentiles, "    ");
+        }
+    }
+}
+
+/// the spread of the times, then each percentile of them
+fn print_stats(times: impl Iterator<Item = Duration>, percentiles: &[f64], indent: &str) {
+    let mut times: Vec<_> = times.collect();
+    times.sort_unstable();
+    let (Some(min), Some(p50), Some(max)) =
+        (times.first(), stats::percentile(&times, 0.5), times.last())
+    else {
+        println!("{indent}no requests");
+        return;
+    };
+
+    let secs: Vec<_> = times.iter().map(Duration::as_secs_f64).collect();
+    let mean = secs.iter().sum::<f64>() / secs.len() as f64;
+    let variance = secs.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / secs.len() as f64;
+    println!(
+        "{indent}count: {}, min: {min:?}, P50: {p50:?}, mean: {:?}, stddev: {:?}, max: {max:?}",
+        times.len(),
+        Duration::from_secs_f64(mean),
+        Duration::from_secs_f64(variance.sqrt()),
+    );
+    for p in percentiles {
+        println!("{indent}P{p}: {}", percentile_time(&times, p / 100.0));
+    }
+}
 
-    match subset.first().map(|v| v.time) {
-        Some(t) => format!("{t:?} ({count}/{n_total})"),
-        None => format!("unable to calculate ({count}/{n_total})"),
+fn percentile_time(times: &[Duration], ratio: f64) -> String {
+    let n_total = times.len();
+    match stats::percentile_index(n_total, ratio) {
+        Some(n) => format!("{:?} ({}/{n_total})", times[n], n_total - n),
+        None => format!("unable to calculate (0/{n_total})"),
     }
 }
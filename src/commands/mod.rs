@@ -1,3 +1,4 @@
+pub(crate) mod compare;
 pub(crate) mod extract;
 pub(crate) mod plot;
 pub(crate) mod spam;
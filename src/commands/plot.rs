@@ -4,22 +4,32 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::TestResult;
+use crate::{response_info::Timing, stats, ResponseInfo, RunSelection, TestResult};
 use anyhow::Result;
 use clap::Parser;
 use ndhistogram::{axis::Uniform, ndhistogram, Histogram};
 use tokio::time::Duration;
 
 #[derive(Parser, Debug)]
-pub(crate) struct Options {}
+pub(crate) struct Options {
+    #[command(flatten)]
+    run: RunSelection,
+}
 
 pub(crate) fn plot(
-    _options: Options,
+    Options { run }: Options,
     names: Option<Vec<String>>,
     data_dir: PathBuf,
     out_dir: PathBuf,
 ) -> Result<()> {
-    for result in TestResult::load_filtered(data_dir, names)? {
+    let run_dir = run.dir(&data_dir)?;
+    // graphs for each run are kept separately
+    let out_dir = match run_dir.strip_prefix(&data_dir) {
+        Ok(run_id) => out_dir.join(run_id),
+        Err(_) => out_dir,
+    };
+
+    for result in TestResult::load_filtered(run_dir, names)? {
         let title = format!("{} Successes Total Latency", result.name);
         let _ = plot_histogram(
             result.success_responses().map(|res| res.time),
@@ -27,6 +37,15 @@ pub(crate) fn plot(
             &out_dir,
         );
 
+        let body_title = format!("{} Successes Latency With Body", result.name);
+        let _ = plot_histogram(
+            result
+                .success_responses()
+                .filter_map(|res| res.total_time()),
+            &body_title,
+            &out_dir,
+        );
+
         let failures_title = format!("{} Failures Total Latency", result.name);
         let _ = plot_histogram(
             result.failure_responses().map(|res| res.time),
@@ -50,6 +69,38 @@ pub(crate) fn plot(
             &req_l_title,
             &out_dir,
         );
+
+        // where the time of each request went
+        for (i, phase) in Timing::PHASES.iter().enumerate() {
+            let title = format!("{} {phase} Phase Latency", result.name);
+            let _ = plot_histogram(
+                result
+                    .responses
+                    .iter()
+                    .filter_map(|r| r.timing.and_then(|t| t.phases()[i])),
+                &title,
+                &out_dir,
+            );
+        }
+
+        let _ = plot_over_time(&result.responses, &result.name, &out_dir);
+
+        // break down a weighted mix by request
+        for name in result.request_names() {
+            let title = format!("{} {name} Successes Total Latency", result.name);
+            let _ = plot_histogram(
+                result
+                    .request_responses(name)
+                    .filter(|r| r.status.is_success())
+                    .map(|res| res.time),
+                &title,
+                &out_dir,
+            );
+        }
+        for name in result.step_names() {
+            let title = format!("{} {name} Step Latency", result.name);
+            let _ = plot_histogram(result.step_times(name), &title, &out_dir);
+        }
     }
 
     Ok(())
@@ -75,7 +126,7 @@ fn plot_histogram<P: AsRef<Path>>(
 
     let highest_count = histogram
         .iter()
-        .map(|v| (*v.value as u32))
+        .map(|v| *v.value as u32)
         .max()
         .ok_or_else(|| anyhow::anyhow!("no max??"))?;
 
@@ -114,6 +165,147 @@ fn plot_histogram<P: AsRef<Path>>(
     Ok(())
 }
 
+/// latency scatter with rolling p50/p99, and achieved vs target throughput, over the course of a test
+fn plot_over_time<P: AsRef<Path>>(
+    responses: &[ResponseInfo],
+    name: &str,
+    out_dir: P,
+) -> Result<()> {
+    let file_path = out_dir.as_ref().join(format!("{name} Over Time.png"));
+    let _ = fs::create_dir_all(out_dir);
+
+    let end = responses
+        .iter()
+        .map(|r| (r.start_offset + r.time).as_secs_f64())
+        .fold(0f64, f64::max);
+    if end <= 0f64 {
+        return Err(anyhow::anyhow!("no responses to plot"));
+    }
+    let max_ms = responses
+        .iter()
+        .map(|r| r.time.as_secs_f64() * 1000f64)
+        .fold(0f64, f64::max)
+        * 1.05;
+
+    // roughly 100 windows, but at least one second each
+    let window = (end / 100f64).max(1f64);
+    let windows = (end / window).ceil() as usize;
+
+    let mut sent = vec![Vec::new(); windows];
+    let mut completed = vec![0usize; windows];
+    let mut targets = vec![Vec::new(); windows];
+    for response in responses {
+        let start = response.start_offset.as_secs_f64();
+        let i = ((start / window) as usize).min(windows - 1);
+        sent[i].push(response.time.as_secs_f64() * 1000f64);
+        if let Some(target) = response.target_rps {
+            targets[i].push(target);
+        }
+        let finish = start + response.time.as_secs_f64();
+        completed[((finish / window) as usize).min(windows - 1)] += 1;
+    }
+
+    let center = |i: usize| (i as f64 + 0.5) * window;
+    let rolling = |ratio: f64| {
+        sent.iter()
+            .enumerate()
+            .filter(|(_, times)| !times.is_empty())
+            .map(|(i, times)| {
+                let mut times = times.clone();
+                times.sort_unstable_by(f64::total_cmp);
+                let p = stats::percentile(&times, ratio).expect("empty windows are skipped");
+                (center(i), p)
+            })
+            .collect::<Vec<_>>()
+    };
+    let achieved: Vec<_> = completed
+        .iter()
+        .enumerate()
+        .map(|(i, count)| (center(i), *count as f64 / window))
+        .collect();
+    let target: Vec<_> = targets
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.is_empty())
+        .map(|(i, t)| (center(i), t.iter().sum::<f64>() / t.len() as f64))
+        .collect();
+    let max_rps = achieved
+        .iter()
+        .chain(target.iter())
+        .map(|(_, rps)| *rps)
+        .fold(0f64, f64::max)
+        * 1.1;
+
+    use plotters::prelude::*;
+
+    let root_drawing_area = BitMapBackend::new(&file_path, (2000, 1500)).into_drawing_area();
+    root_drawing_area.fill(&WHITE)?;
+    let (upper, lower) = root_drawing_area.split_vertically(1000);
+
+    let mut latency = ChartBuilder::on(&upper)
+        .caption(format!("{name} Latency Over Time"), ("Segoe UI", 30))
+        .margin(10)
+        .set_label_area_size(LabelAreaPosition::Left, 60)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(0f64..end, 0f64..max_ms.max(1f64))?;
+    latency
+        .configure_mesh()
+        .x_desc("seconds")
+        .y_desc("latency (ms)")
+        .draw()?;
+
+    latency.draw_series(responses.iter().map(|r| {
+        let point = (r.start_offset.as_secs_f64(), r.time.as_secs_f64() * 1000f64);
+        match r.status.is_success() {
+            true => Circle::new(point, 2, BLUE.mix(0.3).filled()),
+            false => Circle::new(point, 2, RED.mix(0.5).filled()),
+        }
+    }))?;
+    latency
+        .draw_series(LineSeries::new(rolling(0.5), GREEN.stroke_width(3)))?
+        .label("p50")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], GREEN));
+    latency
+        .draw_series(LineSeries::new(rolling(0.99), MAGENTA.stroke_width(3)))?
+        .label("p99")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], MAGENTA));
+    latency
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    let mut throughput = ChartBuilder::on(&lower)
+        .caption(format!("{name} Throughput Over Time"), ("Segoe UI", 30))
+        .margin(10)
+        .set_label_area_size(LabelAreaPosition::Left, 60)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(0f64..end, 0f64..max_rps.max(1f64))?;
+    throughput
+        .configure_mesh()
+        .x_desc("seconds")
+        .y_desc("rps")
+        .draw()?;
+
+    throughput
+        .draw_series(LineSeries::new(achieved, BLUE.stroke_width(3)))?
+        .label("achieved")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+    throughput
+        .draw_series(LineSeries::new(target, BLACK.stroke_width(2)))?
+        .label("target")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK));
+    throughput
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root_drawing_area.present()?;
+
+    Ok(())
+}
+
 fn mean(data: &[u32]) -> Option<f32> {
     let sum = data.iter().sum::<u32>() as f32;
     let count = data.len();
@@ -1,36 +1,55 @@
 use std::{collections::HashMap, str::FromStr};
 
+use anyhow::Result;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
-    Method, RequestBuilder, Url,
+    Method, Url,
 };
 
-use crate::configs::RequestConfig;
+use crate::{
+    configs::{RequestConfig, UrlTemplate},
+    template::{Template, TemplateContext},
+};
 
 pub(super) fn build_reqwest(
     reqwest_client: &reqwest::Client,
-    config: RequestConfig,
-    rotate_uuids: bool,
-) -> RequestBuilder {
-    match config {
-        RequestConfig::Get { url, headers } => reqwest_client
-            .request(Method::GET, configure_url(rotate_uuids, url))
-            .headers(to_header_map(headers)),
-        RequestConfig::Post { url, headers, body } => reqwest_client
-            .request(Method::POST, configure_url(rotate_uuids, url))
-            .headers(to_header_map(headers))
-            .body(body),
-    }
+    config: &RequestConfig,
+    context: &TemplateContext,
+) -> Result<reqwest::Request> {
+    let RequestConfig {
+        method,
+        url,
+        headers,
+        body,
+    } = config;
+
+    let builder = reqwest_client
+        .request(method.clone(), render_url(url, context)?)
+        .headers(to_header_map(headers, context));
+
+    let sends_body = [Method::POST, Method::PUT, Method::PATCH].contains(method);
+    let builder = match body {
+        Some(body) => builder.body(body.render(context)),
+        None if sends_body => builder.body(""),
+        None => builder,
+    };
+
+    Ok(builder.build()?)
 }
 
-fn configure_url(rotate_uuids: bool, mut url: Url) -> Url {
-    if rotate_uuids {
-        let req_uuid = format!("m-{}", uuid::Uuid::new_v4().simple());
-        replace_or_append_query_param(&mut url, "user", &req_uuid);
-        url
-    } else {
-        url
+fn render_url(url: &UrlTemplate, context: &TemplateContext) -> Result<Url> {
+    let render = |params: &[(String, Template)]| -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(name, value)| (name.clone(), value.render(context)))
+            .collect()
+    };
+
+    let mut rendered = Url::parse_with_params(&url.base.render(context), render(&url.query))?;
+    for (name, value) in render(&url.overrides) {
+        replace_or_append_query_param(&mut rendered, &name, &value);
     }
+    Ok(rendered)
 }
 
 fn replace_or_append_query_param(url: &mut Url, name: &str, value: &str) {
@@ -45,13 +64,13 @@ fn replace_or_append_query_param(url: &mut Url, name: &str, value: &str) {
     url.query_pairs_mut().clear().extend_pairs(&query);
 }
 
-fn to_header_map(headers: HashMap<String, String>) -> HeaderMap {
+fn to_header_map(headers: &HashMap<String, Template>, context: &TemplateContext) -> HeaderMap {
     headers
         .iter()
         .map(|(name, val)| {
             (
                 HeaderName::from_str(name.as_ref()),
-                HeaderValue::from_str(val.as_ref()),
+                HeaderValue::from_str(&val.render(context)),
             )
         })
         .filter(|(k, v)| k.is_ok() && v.is_ok())
@@ -1,26 +1,46 @@
 mod cancellation;
 mod create_request;
+mod progress;
 mod test;
 mod test_client;
+mod thresholds;
 
 pub(crate) use cancellation::*;
 use test_client::SpamService;
+use thresholds::Evaluation;
 
 use std::ffi::OsStr;
 use std::fs;
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 
 use crate::configs::SpamConfig;
 use crate::configs::TestConfig;
+use crate::Run;
 
 #[derive(Parser, Debug)]
-pub(crate) struct Options {}
+pub(crate) struct Options {
+    /// label appended to the run id, can be used to select the run in `plot` and `extract`
+    #[arg(long, short)]
+    label: Option<String>,
+
+    /// write threshold results to this path as a JUnit XML report
+    #[arg(long)]
+    junit: Option<PathBuf>,
+
+    /// run the selected tests at the same time instead of one after another
+    #[arg(long)]
+    parallel: bool,
+}
 
 pub(crate) async fn spam(
-    _options: Options,
+    Options {
+        label,
+        junit,
+        parallel,
+    }: Options,
     names: Option<Vec<String>>,
     config_path: PathBuf,
     output_dir: PathBuf,
@@ -33,27 +53,77 @@ pub(crate) async fn spam(
         _ => panic!("Unsupported config file extension"),
     };
 
-    let cancellation = Cancellation::new();
-    tokio::spawn(watch_cancellation(cancellation.clone()));
-
+    // checked before the run is created, so a typo doesn't leave an empty run as the latest
     let test_configs: Vec<TestConfig> = match names {
-        Some(names) => names
-            .into_iter()
-            .filter_map(|n| config.test_configs.iter().find(|t| t.name == n).cloned())
-            .collect(),
+        Some(names) => {
+            let unknown: Vec<_> = names
+                .iter()
+                .filter(|n| !config.test_configs.iter().any(|t| &t.name == *n))
+                .map(String::as_str)
+                .collect();
+            if !unknown.is_empty() {
+                return Err(anyhow!(
+                    "no test named {} in the config",
+                    unknown.join(", ")
+                ));
+            }
+            names
+                .into_iter()
+                .filter_map(|n| config.test_configs.iter().find(|t| t.name == n).cloned())
+                .collect()
+        }
         None => config.test_configs,
     };
+    if test_configs.is_empty() {
+        return Err(anyhow!("the config has no tests to run"));
+    }
+
+    let mut run = Run::create(&output_dir, label, config_path)?;
+    println!("run: {}", run.manifest.id);
 
-    let handles = test_configs
+    let cancellation = Cancellation::new();
+    tokio::spawn(watch_cancellation(cancellation.clone()));
+
+    let test_configs: Vec<_> = test_configs
         .into_iter()
-        .map(|test_config| test_config.resolve(&config.global))
-        .map(|test_config| test::test(test_config, cancellation.clone()));
-
-    for handle in handles {
-        let result = handle.await;
-        println!("{}", result.report());
-        if let Err(e) = result.save(&output_dir) {
-            println!("Error saving results for '{}': {e}", result.name);
+        .map(|t| t.resolve(&config.global))
+        .collect();
+    // parallel tests run as a single group
+    let groups = match parallel || config.global.parallel {
+        true => vec![test_configs],
+        false => test_configs.into_iter().map(|t| vec![t]).collect(),
+    };
+
+    let mut evaluations = vec![];
+    let mut errored = vec![];
+    for group in groups {
+        let tests: Vec<_> = group
+            .iter()
+            .map(|t| (t.name.clone(), t.thresholds.clone()))
+            .collect();
+        for test_config in &group {
+            run.add_test(test_config.name.clone())?;
+        }
+
+        let summaries = test::test_all(group, cancellation.clone(), &run.dir).await;
+        for (summary, (name, thresholds)) in summaries.into_iter().zip(tests) {
+            let summary = match summary {
+                Ok(summary) => summary,
+                Err(e) => {
+                    println!("{name}:\n    error: {e:#}\n");
+                    if thresholds.is_some() {
+                        evaluations.push(Evaluation::errored(name.clone(), format!("{e:#}")));
+                    }
+                    errored.push(name);
+                    continue;
+                }
+            };
+            println!("{}", summary.report());
+            if let Some(thresholds) = thresholds {
+                let evaluation = thresholds.evaluate(&summary);
+                println!("{}", evaluation.table());
+                evaluations.push(evaluation);
+            }
         }
 
         if cancellation.is_canceled() {
@@ -61,5 +131,25 @@ pub(crate) async fn spam(
         }
     }
 
-    Ok(())
+    if let Some(junit) = junit {
+        thresholds::write_junit(junit, &run.manifest.id, &evaluations)?;
+    }
+
+    // tests that couldn't run aren't also reported as breaching their thresholds
+    let breached: Vec<_> = evaluations
+        .iter()
+        .filter(|e| e.error.is_none() && !e.passed())
+        .map(|e| e.test.as_str())
+        .collect();
+    let mut problems = vec![];
+    if !errored.is_empty() {
+        problems.push(format!("unable to run {}", errored.join(", ")));
+    }
+    if !breached.is_empty() {
+        problems.push(format!("thresholds breached for {}", breached.join(", ")));
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(problems.join("; "))),
+    }
 }
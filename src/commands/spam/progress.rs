@@ -0,0 +1,109 @@
+use crossterm::{cursor, terminal, QueueableCommand};
+use std::io::Write;
+use std::ops::Div;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, OnceLock,
+};
+use tokio::time::{Duration, Instant};
+
+/// the progress of a single test, updated by the test and drawn by `Progress`
+pub(crate) struct Bar {
+    name: String,
+    duration: Option<Duration>,
+    /// when the test started sending and how many requests it expects to send
+    started: OnceLock<(Instant, usize)>,
+    complete: AtomicUsize,
+    finished: AtomicBool,
+}
+
+impl Bar {
+    pub fn start(&self, count: usize) {
+        let _ = self.started.set((Instant::now(), count));
+    }
+
+    pub fn inc(&self) {
+        self.complete.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    fn line(&self) -> String {
+        static VISUAL: &str = "====================>...................";
+        let Some((start, count)) = self.started.get() else {
+            return format!("[{}] starting", self.name);
+        };
+        let complete = self.complete.load(Ordering::Relaxed);
+        let length = VISUAL.len();
+        let elapsed = start.elapsed();
+        let ratio = match self.duration {
+            Some(duration) => elapsed.as_secs_f64() / duration.as_secs_f64(),
+            None => complete as f64 / *count as f64,
+        }
+        .clamp(0f64, 1f64);
+        let chunks = (length as f64 * ratio).floor().div(2f64) as usize;
+        let visual = &VISUAL[(length / 2) - chunks..length - chunks];
+        match self.duration {
+            Some(duration) => format!(
+                "[{}] [{visual}] {complete:>8} {:>6}s elapsed, {:>6}s remaining",
+                self.name,
+                elapsed.as_secs(),
+                duration.saturating_sub(elapsed).as_secs()
+            ),
+            None => format!("[{}] [{visual}] {complete:>8}/{count}", self.name),
+        }
+    }
+}
+
+/// draws a line for each running test
+#[derive(Default)]
+pub(crate) struct Progress {
+    bars: Vec<Arc<Bar>>,
+}
+
+impl Progress {
+    pub fn add(&mut self, name: String, duration: Option<Duration>) -> Arc<Bar> {
+        let bar = Arc::new(Bar {
+            name,
+            duration,
+            started: OnceLock::new(),
+            complete: AtomicUsize::new(0),
+            finished: AtomicBool::new(false),
+        });
+        self.bars.push(bar.clone());
+        bar
+    }
+
+    /// redraw the bars until every test has finished, then clear them
+    pub async fn draw(self) {
+        let mut stdout = std::io::stdout();
+        let lines = self.bars.len() as u16;
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        let _ = stdout.queue(cursor::Hide);
+        let mut drawn = false;
+        loop {
+            interval.tick().await;
+            let finished = self.bars.iter().all(|b| b.finished.load(Ordering::Relaxed));
+            if drawn {
+                let _ = stdout.queue(cursor::MoveUp(lines));
+            }
+            for bar in &self.bars {
+                let _ = stdout.queue(cursor::MoveToColumn(0));
+                let _ = write!(stdout, "{}", bar.line());
+                let _ = stdout.queue(terminal::Clear(terminal::ClearType::UntilNewLine));
+                let _ = writeln!(stdout);
+            }
+            drawn = true;
+            let _ = stdout.flush();
+            if finished {
+                break;
+            }
+        }
+        let _ = stdout.queue(cursor::MoveUp(lines));
+        let _ = stdout.queue(terminal::Clear(terminal::ClearType::FromCursorDown));
+        let _ = stdout.queue(cursor::Show);
+        let _ = stdout.flush();
+    }
+}
@@ -1,52 +1,70 @@
-use crossterm::{cursor, terminal, QueueableCommand};
-use futures::StreamExt;
-use std::io::{Stdout, Write};
-use std::ops::Div;
+use futures::{future::join_all, StreamExt};
+use std::path::Path;
 use tokio::time::Instant;
 
+use super::progress::{Bar, Progress};
 use super::{Cancellation, SpamService};
-use crate::{configs::ResolvedConfig, test_result::TestResult};
+use crate::{configs::ResolvedConfig, ResultWriter, TestSummary};
+use anyhow::{Context, Result};
 
-pub(crate) async fn test(config: ResolvedConfig, cancellation: Cancellation) -> TestResult {
-    let start = Instant::now();
-    let count = config.count;
-    let mut service =
-        SpamService::new(config.clone(), cancellation).expect("unable to build spam service");
-    let mut stream = service.run_test().await;
-
-    // let mut buffered = tokio_stream::iter(stream).buffer_unordered(parallelism);
+/// run tests at the same time, each with its own limits, drawing their progress together, a test
+/// that fails doesn't stop the others
+pub(crate) async fn test_all(
+    configs: Vec<ResolvedConfig>,
+    cancellation: Cancellation,
+    output_dir: &Path,
+) -> Vec<Result<TestSummary>> {
+    let mut progress = Progress::default();
+    let tests: Vec<_> = configs
+        .into_iter()
+        .map(|config| {
+            let bar = progress.add(config.name.clone(), config.duration);
+            let cancellation = cancellation.clone();
+            async move {
+                let summary = test(config, cancellation, output_dir, &bar).await;
+                bar.finish();
+                summary
+            }
+        })
+        .collect();
 
-    let mut results = Vec::with_capacity(count);
-
-    let mut stdout = std::io::stdout();
-    let _ = writeln!(stdout, "[{}]", config.name);
-    let mut complete = 0usize;
+    let (summaries, _) = tokio::join!(join_all(tests), progress.draw());
+    summaries
+}
 
-    print_progress(&mut stdout, complete, count);
+/// run a test, streaming its results to `<output_dir>/<name>.chunks`
+async fn test(
+    config: ResolvedConfig,
+    cancellation: Cancellation,
+    output_dir: &Path,
+    bar: &Bar,
+) -> Result<TestSummary> {
+    let mut service = SpamService::new(config.clone(), cancellation)
+        .with_context(|| format!("unable to build spam service for {}", config.name))?;
+    let mut writer = Some(ResultWriter::create(output_dir, &config.name)?);
+    let mut summary = TestSummary::new(config.name.clone());
+    let start = Instant::now();
+    bar.start(service.count());
+    let mut stream = service.run_test().await;
 
     while let Some(result) = stream.next().await {
-        results.push(result);
-
-        complete += 1;
-        if complete % 10 == 0 {
-            print_progress(&mut stdout, complete, count);
+        summary.add(&result);
+        // a failed write leaves a partial chunk, so keep what was written and only count the rest
+        if let Some(Err(e)) = writer.as_mut().map(|w| w.push(result)) {
+            warn_unsaved(&config.name, e);
+            writer = None;
         }
+        bar.inc();
     }
-    let _ = stdout.queue(cursor::MoveUp(1));
-    let _ = stdout.queue(terminal::Clear(terminal::ClearType::FromCursorDown));
 
-    TestResult::new(results, config.name.clone(), start.elapsed())
+    let total_time = start.elapsed();
+    if let Some(Err(e)) = writer.map(|w| w.finish(total_time)) {
+        warn_unsaved(&config.name, e);
+    }
+    summary.finish(total_time);
+    Ok(summary)
 }
-fn print_progress(stdout: &mut Stdout, complete: usize, count: usize) {
-    static VISUAL: &str = "====================>...................";
-    let length = VISUAL.len();
-    let ratio = (complete as f64 / count as f64).clamp(0f64, 1f64);
-    let chunks = (length as f64 * ratio).floor().div(2f64) as usize;
-    let start = (length / 2) - chunks;
-    let end = length - chunks;
-    let _ = stdout.queue(cursor::Hide);
-    let _ = stdout.queue(cursor::SavePosition);
-    let _ = write!(stdout, "[{}] {complete:>8}/{count}", &VISUAL[start..end]);
-    let _ = stdout.queue(cursor::RestorePosition);
-    let _ = stdout.flush();
+
+fn warn_unsaved(test: &str, error: anyhow::Error) {
+    eprintln!("warning: unable to save results for {test}, only those already written are kept: {error:#}");
 }
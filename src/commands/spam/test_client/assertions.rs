@@ -0,0 +1,191 @@
+use std::{fmt::Display, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use jsonschema::Validator;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+use crate::{
+    configs::{BodyAssertion, Comparison, HeaderAssertion},
+    response_info::SchemaViolation,
+};
+
+pub(crate) fn load_schema(path: &Path) -> Result<Validator> {
+    let file = fs::read_to_string(path)
+        .with_context(|| format!("unable to read schema {}", path.display()))?;
+    let schema = serde_json::from_str(&file)
+        .with_context(|| format!("schema {} isn't JSON", path.display()))?;
+    jsonschema::validator_for(&schema)
+        .map_err(|e| anyhow!("invalid schema {}: {e}", path.display()))
+}
+
+/// longest offending value quoted in a violation, jsonschema quotes it whole which can be the whole body
+const MAX_VALUE_LENGTH: usize = 60;
+/// longest violation message kept
+const MAX_MESSAGE_LENGTH: usize = 200;
+
+/// every part of the body that doesn't match the schema
+pub(crate) fn violations(schema: &Validator, json: &Value) -> Vec<SchemaViolation> {
+    schema
+        .iter_errors(json)
+        .map(|e| {
+            let value = e.instance.to_string();
+            let message = e.to_string();
+            // most messages start with the offending value
+            let message = match message.strip_prefix(&value) {
+                Some(rest) => shorten(value, MAX_VALUE_LENGTH) + rest,
+                None => message,
+            };
+            SchemaViolation {
+                pointer: e.instance_path.to_string(),
+                message: shorten(message, MAX_MESSAGE_LENGTH),
+            }
+        })
+        .collect()
+}
+
+fn shorten(mut text: String, length: usize) -> String {
+    if let Some((end, _)) = text.char_indices().nth(length) {
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+impl BodyAssertion {
+    /// whether the assertion queries the body as JSON
+    pub fn reads_json(&self) -> bool {
+        !matches!(self, Self::Regex { .. } | Self::NotContains { .. })
+    }
+
+    /// check the body, describing the assertion and what was found when it fails
+    pub fn check(&self, text: &str, json: Option<&Value>) -> Result<(), String> {
+        let json = match (self.reads_json(), json) {
+            (true, None) => return Err(format!("{self} (body isn't JSON)")),
+            (_, json) => json.unwrap_or(&Value::Null),
+        };
+        let failed = |found: String| Err(format!("{self} (found {found})"));
+
+        match self {
+            Self::Exists { path } => match path.query(json).is_empty() {
+                true => failed("nothing".into()),
+                false => Ok(()),
+            },
+            Self::Equals { path, value } => match path.query(json).first() {
+                Some(found) if found == value => Ok(()),
+                Some(found) => failed(found.to_string()),
+                None => failed("nothing".into()),
+            },
+            Self::Count { path, min, max } => {
+                let nodes = path.query(json);
+                // a single array counts its elements
+                let count = match nodes.exactly_one() {
+                    Ok(Value::Array(items)) => items.len(),
+                    _ => nodes.len(),
+                };
+                let within =
+                    min.is_none_or(|min| count >= min) && max.is_none_or(|max| count <= max);
+                match within {
+                    true => Ok(()),
+                    false => failed(count.to_string()),
+                }
+            }
+            Self::Compare { path, op, value } => match path.query(json).first() {
+                Some(found) => match found.as_f64() {
+                    Some(number) if op.compare(number, *value) => Ok(()),
+                    _ => failed(found.to_string()),
+                },
+                None => failed("nothing".into()),
+            },
+            Self::Regex { pattern } => match pattern.is_match(text) {
+                true => Ok(()),
+                false => Err(self.to_string()),
+            },
+            Self::NotContains { value } => match text.contains(value.as_str()) {
+                true => Err(self.to_string()),
+                false => Ok(()),
+            },
+        }
+    }
+}
+
+impl Display for BodyAssertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exists { path } => write!(f, "{path} exists"),
+            Self::Equals { path, value } => write!(f, "{path} == {value}"),
+            Self::Count { path, min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "count({path}) in {min}..={max}"),
+                (Some(min), None) => write!(f, "count({path}) >= {min}"),
+                (None, Some(max)) => write!(f, "count({path}) <= {max}"),
+                (None, None) => write!(f, "count({path})"),
+            },
+            Self::Compare { path, op, value } => write!(f, "{path} {op} {value}"),
+            Self::Regex { pattern } => write!(f, "matches /{pattern}/"),
+            Self::NotContains { value } => write!(f, "doesn't contain {value:?}"),
+        }
+    }
+}
+
+impl HeaderAssertion {
+    /// check the headers, describing the assertion and what was found when it fails
+    pub fn check(&self, headers: &HeaderMap) -> Result<(), String> {
+        let (Self::Exists { header }
+        | Self::Equals { header, .. }
+        | Self::Regex { header, .. }
+        | Self::Absent { header }) = self;
+        let found = headers
+            .get(header)
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
+
+        let passed = match (self, &found) {
+            (Self::Absent { .. }, found) => found.is_none(),
+            (_, None) => false,
+            (Self::Exists { .. }, Some(_)) => true,
+            (Self::Equals { value, .. }, Some(found)) => found == value,
+            (Self::Regex { pattern, .. }, Some(found)) => pattern.is_match(found),
+        };
+        match (passed, found) {
+            (true, _) => Ok(()),
+            (false, Some(found)) => Err(format!("{self} (found {found:?})")),
+            (false, None) => Err(format!("{self} (found nothing)")),
+        }
+    }
+}
+
+impl Display for HeaderAssertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exists { header } => write!(f, "{header} exists"),
+            Self::Equals { header, value } => write!(f, "{header} == {value:?}"),
+            Self::Regex { header, pattern } => write!(f, "{header} matches /{pattern}/"),
+            Self::Absent { header } => write!(f, "{header} is absent"),
+        }
+    }
+}
+
+impl Comparison {
+    fn compare(self, a: f64, b: f64) -> bool {
+        match self {
+            Self::Lt => a < b,
+            Self::Le => a <= b,
+            Self::Eq => a == b,
+            Self::Ne => a != b,
+            Self::Ge => a >= b,
+            Self::Gt => a > b,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Ge => ">=",
+            Self::Gt => ">",
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use rand::Rng;
+
+use crate::configs::{FeederConfig, FeederStrategy};
+
+pub(crate) type Row = Arc<HashMap<String, String>>;
+
+/// rows of template variables loaded from a file
+pub(crate) struct Feeder {
+    rows: Vec<Row>,
+    strategy: FeederStrategy,
+    path: PathBuf,
+    /// whether a `sequential` feeder has run out, so it's only reported once
+    exhausted: AtomicBool,
+}
+
+impl Feeder {
+    pub fn load(config: &FeederConfig) -> Result<Self> {
+        let path = &config.path;
+        let rows = match path.extension().and_then(OsStr::to_str) {
+            Some("csv") => csv::Reader::from_path(path)?
+                .deserialize::<HashMap<String, String>>()
+                .collect::<Result<Vec<_>, _>>()?,
+            Some("jsonl") => fs::read_to_string(path)?
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(|l| {
+                    let row: serde_json::Map<String, serde_json::Value> = serde_json::from_str(l)?;
+                    Ok(row
+                        .into_iter()
+                        .map(|(k, v)| match v {
+                            serde_json::Value::String(s) => (k, s),
+                            v => (k, v.to_string()),
+                        })
+                        .collect())
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => {
+                return Err(anyhow!(
+                    "feeder {} must be a .csv or .jsonl file",
+                    path.display()
+                ))
+            }
+        };
+        if rows.is_empty() {
+            return Err(anyhow!("feeder {} has no rows", path.display()));
+        }
+
+        Ok(Self {
+            rows: rows.into_iter().map(Arc::new).collect(),
+            strategy: config.strategy,
+            path: path.clone(),
+            exhausted: AtomicBool::new(false),
+        })
+    }
+
+    /// check every variable is supplied by every row
+    pub fn check_variables<'a>(&self, variables: impl Iterator<Item = &'a str>) -> Result<()> {
+        let variables: Vec<_> = variables.collect();
+        for (i, row) in self.rows.iter().enumerate() {
+            if let Some(missing) = variables.iter().find(|v| !row.contains_key(**v)) {
+                return Err(anyhow!(
+                    "feeder row {} has no '{missing}' value for {{{{{missing}}}}}",
+                    i + 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// check a `sequential` feeder has a row for each of the `count` requests of a test
+    pub fn check_count(&self, count: usize) -> Result<()> {
+        match self.strategy {
+            FeederStrategy::Sequential if count > self.rows.len() => Err(anyhow!(
+                "feeder {} has {} rows for {count} requests, use `circular` to reuse rows or \
+                 `stop_when_exhausted` to stop when they run out",
+                self.path.display(),
+                self.rows.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// the number of requests the feeder can supply
+    pub fn limit(&self) -> Option<usize> {
+        match self.strategy {
+            FeederStrategy::StopWhenExhausted => Some(self.rows.len()),
+            FeederStrategy::Circular | FeederStrategy::Sequential | FeederStrategy::Random => None,
+        }
+    }
+
+    /// the row for the `seq`th request, `None` once a `stop_when_exhausted` feeder is exhausted
+    pub fn row(&self, seq: usize) -> Option<Row> {
+        match self.strategy {
+            FeederStrategy::Circular => Some(self.rows[seq % self.rows.len()].clone()),
+            FeederStrategy::Sequential => {
+                if seq >= self.rows.len() && !self.exhausted.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "warning: feeder {} ran out of rows, the last row is reused for the rest of the test",
+                        self.path.display()
+                    );
+                }
+                Some(self.rows[seq.min(self.rows.len() - 1)].clone())
+            }
+            FeederStrategy::StopWhenExhausted => self.rows.get(seq).cloned(),
+            FeederStrategy::Random => {
+                Some(self.rows[rand::thread_rng().gen_range(0..self.rows.len())].clone())
+            }
+        }
+    }
+}
@@ -1,100 +1,436 @@
+mod assertions;
+mod feeder;
+mod replay;
+mod scheduler;
 mod test_service;
+mod timing;
+mod transport;
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{ready as ready_poll, Context, Poll},
+};
 
-use futures::{future::ready, Stream, StreamExt};
+use assertions::load_schema;
+use feeder::Feeder;
+use futures::{future::ready, Future, FutureExt, Stream, StreamExt};
+use jsonschema::Validator;
+use rand::distributions::{Distribution, WeightedIndex};
+use replay::Replay;
+use scheduler::{Scheduler, Slot};
 use test_service::*;
+use transport::{Resolver, Sender};
 
-use crate::{configs::ResolvedConfig, response_info::ResponseInfo};
-use anyhow::Result;
-use tokio::time::Duration;
+use crate::{
+    configs::{LoadStage, RequestConfig, ResolvedConfig, Step, WeightedRequest},
+    response_info::ResponseInfo,
+};
+use anyhow::{anyhow, Result};
+use tokio::{
+    sync::Semaphore,
+    time::{sleep_until, Duration, Instant, Sleep},
+};
 use tower::{
     buffer::Buffer,
-    limit::{ConcurrencyLimit, RateLimit},
-    ServiceExt,
+    limit::{ConcurrencyLimit, RateLimit, RateLimitLayer},
+    util::Either,
+    Layer, Service, ServiceExt,
 };
 
 use super::Cancellation;
 
+/// the `TestService`, rate limited unless the test follows a load profile
+type RateLimitedService = Either<RateLimit<TestService<Sender>>, TestService<Sender>>;
+
+/// where the requests for a test come from
+#[derive(Clone)]
+enum Requests {
+    Single(RequestConfig),
+    Replay(Arc<Replay>),
+    Mix(Arc<Mix>),
+    Scenario(Arc<Vec<Step>>),
+}
+
+/// named requests picked at random in proportion to their weights
+struct Mix {
+    requests: Vec<WeightedRequest>,
+    weights: WeightedIndex<u32>,
+}
+
+/// the next request for the stream to send
+struct Next {
+    flow: Flow,
+    /// the name of the request in a weighted mix
+    name: Option<String>,
+    /// when replaying with timing, the offset from the start of the test to send at
+    offset: Option<Duration>,
+}
+
+impl Requests {
+    fn new(config: &ResolvedConfig) -> Result<Self> {
+        let sources = [
+            config.request.is_some(),
+            config.requests.is_some(),
+            config.scenario.is_some(),
+            config.replay.is_some(),
+        ];
+        if sources.into_iter().filter(|s| *s).count() != 1 {
+            return Err(anyhow!(
+                "a test needs exactly one of `request`, `requests`, `scenario` or `replay`"
+            ));
+        }
+
+        match (
+            &config.request,
+            &config.replay,
+            &config.requests,
+            &config.scenario,
+        ) {
+            (Some(request), ..) => Ok(Self::Single(request.clone())),
+            (_, Some(replay), ..) => Ok(Self::Replay(Arc::new(Replay::load(replay)?))),
+            (_, _, Some(requests), _) => {
+                let weights = WeightedIndex::new(requests.iter().map(|r| r.weight))
+                    .map_err(|e| anyhow!("invalid request weights: {e}"))?;
+                Ok(Self::Mix(Arc::new(Mix {
+                    requests: requests.clone(),
+                    weights,
+                })))
+            }
+            (.., Some(steps)) if steps.is_empty() => Err(anyhow!("a scenario needs a step")),
+            (.., Some(steps)) => Ok(Self::Scenario(Arc::new(steps.clone()))),
+            (None, None, None, None) => unreachable!("checked above"),
+        }
+    }
+
+    fn variables(&self) -> Vec<&str> {
+        match self {
+            Self::Single(request) => request.variables().collect(),
+            Self::Replay(_) => vec![],
+            Self::Mix(mix) => mix
+                .requests
+                .iter()
+                .flat_map(|r| r.request.variables())
+                .collect(),
+            // variables extracted by earlier steps don't need a feeder
+            Self::Scenario(steps) => {
+                let mut variables = vec![];
+                for (i, step) in steps.iter().enumerate() {
+                    let extracted =
+                        |v: &&str| steps[..i].iter().any(|s| s.extract.contains_key(*v));
+                    variables.extend(step.request.variables().filter(|v| !extracted(v)));
+                }
+                variables
+            }
+        }
+    }
+
+    /// the `seq`th request, `None` once a replay is exhausted
+    fn get(&self, seq: usize) -> Option<Next> {
+        match self {
+            Self::Single(request) => Some(Next {
+                flow: Flow::Request(request.clone()),
+                name: None,
+                offset: None,
+            }),
+            Self::Replay(replay) => replay.entries.get(seq).map(|entry| Next {
+                flow: Flow::Request(entry.request.clone()),
+                name: None,
+                offset: replay.preserve_timing.then_some(entry.offset),
+            }),
+            Self::Mix(mix) => {
+                let picked = &mix.requests[mix.weights.sample(&mut rand::thread_rng())];
+                Some(Next {
+                    flow: Flow::Request(picked.request.clone()),
+                    name: Some(picked.name.clone()),
+                    offset: None,
+                })
+            }
+            Self::Scenario(steps) => Some(Next {
+                flow: Flow::Scenario(steps.clone()),
+                name: None,
+                offset: None,
+            }),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct SpamService {
-    service: Buffer<ConcurrencyLimit<RateLimit<TestService<reqwest::Client>>>, ResolvedConfig>,
+    service: Buffer<ConcurrencyLimit<RateLimitedService>, TestRequest>,
+    client: reqwest::Client,
+    sender: Sender,
     config: ResolvedConfig,
+    requests: Requests,
+    feeder: Option<Arc<Feeder>>,
+    schema: Option<Arc<Validator>>,
     cancellation: Cancellation,
 }
 
 impl SpamService {
     pub(crate) fn new(config: ResolvedConfig, cancellation: Cancellation) -> Result<Self> {
+        let requests = Requests::new(&config)?;
+        let variables = requests.variables();
+        let feeder = match &config.feeder {
+            Some(feeder_config) => {
+                let feeder = Feeder::load(feeder_config)?;
+                feeder.check_variables(variables.into_iter())?;
+                Some(Arc::new(feeder))
+            }
+            None => {
+                if let Some(name) = variables.first() {
+                    return Err(anyhow!("{{{{{name}}}}} needs a feeder to supply it"));
+                }
+                None
+            }
+        };
+
+        let schema = match &config.schema {
+            Some(path) => Some(Arc::new(load_schema(path)?)),
+            None => None,
+        };
+
         let client = reqwest::Client::builder()
             .timeout(config.timeout)
             .pool_idle_timeout(Duration::from_secs(10))
+            .dns_resolver(Arc::new(Resolver))
             .build()?;
+        let sender = Sender::new(&config, &client)?;
 
         let service = tower::ServiceBuilder::new()
             .buffer(100)
             .concurrency_limit(config.max_concurrent)
-            .rate_limit(config.max_rps as u64, Duration::from_secs(1))
-            .layer(TestLayer::new(client.clone()))
-            .service(client);
+            // a load profile paces requests itself
+            .option_layer(
+                config
+                    .load_profile
+                    .is_none()
+                    .then(|| RateLimitLayer::new(config.max_rps as u64, Duration::from_secs(1))),
+            )
+            .layer(TestLayer::new(client.clone(), schema.clone()))
+            .service(sender.clone());
 
-        Ok(Self {
+        let service = Self {
             service,
+            client,
+            sender,
             config,
+            requests,
+            feeder,
+            schema,
             cancellation,
-        })
+        };
+        // a test with a duration can't know how many rows it needs
+        if let (Some(feeder), None) = (&service.feeder, service.config.duration) {
+            feeder.check_count(service.count())?;
+        }
+        Ok(service)
     }
 
-    pub async fn run_test(&'_ mut self) -> impl Stream<Item = ResponseInfo> + '_ {
-        let stream = ConfigStream {
-            config: self.config.clone(),
-            count: AtomicUsize::new(self.config.count),
-            cancellation: self.cancellation.clone(),
+    /// the most requests the test will send when it isn't limited by a duration
+    pub fn count(&self) -> usize {
+        let replayed = match &self.requests {
+            Requests::Single(_) | Requests::Mix(_) | Requests::Scenario(_) => None,
+            Requests::Replay(replay) => Some(replay.entries.len()),
         };
+        [
+            Some(self.config.count),
+            replayed,
+            self.feeder.as_ref().and_then(|f| f.limit()),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or_default()
+    }
+
+    pub async fn run_test(&'_ mut self) -> impl Stream<Item = ResponseInfo> + '_ {
+        let stream = ConfigStream::new(
+            self.config.clone(),
+            self.requests.clone(),
+            self.feeder.clone(),
+            self.cancellation.clone(),
+        );
+
+        // the open model sends each request on schedule without waiting for earlier requests,
+        // dropping those that would go over the in-flight limit
+        if self.config.open_model {
+            let service =
+                TestLayer::new(self.client.clone(), self.schema.clone()).layer(self.sender.clone());
+            let limit = self.config.max_in_flight;
+            let in_flight = Arc::new(Semaphore::new(limit));
+            return stream
+                .map(move |request| match in_flight.clone().try_acquire_owned() {
+                    Ok(permit) => {
+                        let sending = service.clone().call(request);
+                        tokio::spawn(async move {
+                            let info = sending.await;
+                            drop(permit);
+                            info
+                        })
+                        .left_future()
+                    }
+                    Err(_) => ready(Ok(Ok(request.dropped(limit)))).right_future(),
+                })
+                .buffer_unordered(usize::MAX)
+                .filter_map(|v| ready(v.ok().and_then(|v| v.ok())))
+                .left_stream();
+        }
+
         let svc = self
             .service
             .ready()
             .await
             .expect("unable to wait for service ready");
 
-        svc.call_all(stream).filter_map(|v| ready(v.ok()))
+        svc.call_all(stream)
+            .filter_map(|v| ready(v.ok()))
+            .right_stream()
     }
 }
 
 struct ConfigStream {
     config: ResolvedConfig,
+    requests: Requests,
+    feeder: Option<Arc<Feeder>>,
     cancellation: Cancellation,
-    count: AtomicUsize,
+    count: usize,
+    /// when set the stream runs until this instant instead of until `count` is reached
+    deadline: Option<Instant>,
+    /// when set requests are issued following a load profile
+    scheduler: Option<Scheduler>,
+    start: Instant,
+    sent: usize,
+    /// waits for the next replayed request's original offset
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl ConfigStream {
+    fn new(
+        config: ResolvedConfig,
+        requests: Requests,
+        feeder: Option<Arc<Feeder>>,
+        cancellation: Cancellation,
+    ) -> Self {
+        let start = Instant::now();
+        let scheduler = match (&config.load_profile, config.open_model) {
+            (Some(stages), open_model) => Some(Scheduler::new(stages.clone(), start, open_model)),
+            (None, true) => {
+                let flat = LoadStage::Hold {
+                    rps: config.max_rps as f64,
+                    duration: Duration::MAX,
+                };
+                Some(Scheduler::new(vec![flat], start, true))
+            }
+            (None, false) => None,
+        };
+
+        Self {
+            count: config.count,
+            deadline: config.duration.map(|d| start + d),
+            scheduler,
+            start,
+            sent: 0,
+            delay: None,
+            config,
+            requests,
+            feeder,
+            cancellation,
+        }
+    }
+
+    fn request(
+        &self,
+        Next { flow, name, .. }: Next,
+        vars: Option<feeder::Row>,
+        target_rps: Option<f64>,
+        intended_start: Option<Duration>,
+    ) -> TestRequest {
+        TestRequest {
+            config: self.config.clone(),
+            flow,
+            request_name: name,
+            seq: self.sent,
+            vars,
+            target_rps,
+            test_start: self.start,
+            intended_start,
+        }
+    }
 }
 
 impl Stream for ConfigStream {
-    type Item = ResolvedConfig;
+    type Item = TestRequest;
 
     fn poll_next(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
-        let count = self.count.load(Ordering::Relaxed);
-        if count > 0 && !self.cancellation.is_canceled() {
-            match self.count.compare_exchange(
-                count,
-                count - 1,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => std::task::Poll::Ready(Some(self.config.clone())),
-                Err(_) => {
-                    cx.waker().wake_by_ref();
-                    std::task::Poll::Pending
-                }
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.cancellation.is_canceled() {
+            return Poll::Ready(None);
+        }
+
+        let Some(next) = self.requests.get(self.sent) else {
+            return Poll::Ready(None);
+        };
+        let offset = next.offset;
+
+        // replayed requests wait for their original offset, or the end of the test
+        if let Some(offset) = offset {
+            let at = self
+                .deadline
+                .map_or(self.start + offset, |d| d.min(self.start + offset));
+            if at > Instant::now() {
+                let delay = self.delay.get_or_insert_with(|| Box::pin(sleep_until(at)));
+                delay.as_mut().reset(at);
+                ready_poll!(delay.as_mut().poll(cx));
             }
-        } else {
-            std::task::Poll::Ready(None)
         }
+
+        let slot = match &mut self.scheduler {
+            Some(scheduler) => match ready_poll!(scheduler.poll_slot(cx)) {
+                Some(slot) => Some(slot),
+                None => return Poll::Ready(None),
+            },
+            None => None,
+        };
+
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => return Poll::Ready(None),
+            Some(_) => {}
+            None if self.count == 0 => return Poll::Ready(None),
+            None => self.count -= 1,
+        }
+
+        // taken once the request is certain to be sent, a `stop_when_exhausted` feeder ends the test
+        // once every row has been sent
+        let vars = match &self.feeder {
+            Some(feeder) => match feeder.row(self.sent) {
+                Some(row) => Some(row),
+                None => return Poll::Ready(None),
+            },
+            None => None,
+        };
+
+        // without a scheduler requests are intended to be evenly spaced at `max_rps`
+        let (target_rps, intended_start) = match (slot, offset) {
+            (Some(Slot { rate, intended }), _) => (rate, intended),
+            (None, Some(offset)) => (self.config.max_rps as f64, offset),
+            (None, None) => {
+                let rate = self.config.max_rps as f64;
+                (rate, Duration::from_secs_f64(self.sent as f64 / rate))
+            }
+        };
+        let request = self.request(next, vars, Some(target_rps), Some(intended_start));
+        self.sent += 1;
+
+        Poll::Ready(Some(request))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let count = self.count.load(Ordering::Relaxed);
-
-        (count, Some(count))
+        match self.deadline {
+            Some(_) => (0, None),
+            None => (0, Some(self.count)),
+        }
     }
 }
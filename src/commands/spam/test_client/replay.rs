@@ -0,0 +1,197 @@
+use std::{collections::HashMap, ffi::OsStr, fs, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    configs::{parse_method, ReplayConfig, RequestConfig, UrlTemplate},
+    template::Template,
+};
+
+/// requests read from a capture file
+pub(crate) struct Replay {
+    pub entries: Vec<ReplayEntry>,
+    pub preserve_timing: bool,
+}
+
+pub(crate) struct ReplayEntry {
+    pub request: RequestConfig,
+    /// when the request was sent relative to the first request in the capture
+    pub offset: Duration,
+}
+
+/// a line of a `.jsonl` capture
+#[derive(Deserialize)]
+struct JsonlEntry {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    /// rfc3339 time the request was sent
+    timestamp: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    request: HarRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct HarPostData {
+    text: Option<String>,
+}
+
+impl Replay {
+    pub fn load(config: &ReplayConfig) -> Result<Self> {
+        let path = &config.path;
+        let file = fs::read_to_string(path)?;
+        let entries = match path.extension().and_then(OsStr::to_str) {
+            Some("har") => {
+                let har: Har = serde_json::from_str(&file)?;
+                har.log
+                    .entries
+                    .into_iter()
+                    .map(
+                        |HarEntry {
+                             started_date_time,
+                             request,
+                         }| JsonlEntry {
+                            method: request.method,
+                            url: request.url,
+                            headers: request
+                                .headers
+                                .into_iter()
+                                .map(|h| (h.name, h.value))
+                                .collect(),
+                            body: request.post_data.and_then(|p| p.text),
+                            timestamp: Some(started_date_time),
+                        },
+                    )
+                    .collect()
+            }
+            Some("jsonl") => file
+                .lines()
+                .enumerate()
+                .filter(|(_, l)| !l.trim().is_empty())
+                .map(|(i, l)| serde_json::from_str(l).with_context(|| format!("line {}", i + 1)))
+                .collect::<Result<Vec<JsonlEntry>>>()?,
+            _ => {
+                return Err(anyhow!(
+                    "replay {} must be a .har or .jsonl file",
+                    path.display()
+                ))
+            }
+        };
+        if entries.is_empty() {
+            return Err(anyhow!("replay {} has no requests", path.display()));
+        }
+
+        let mut entries = entries
+            .into_iter()
+            .map(|entry| {
+                let sent_at = entry
+                    .timestamp
+                    .as_deref()
+                    .map(humantime::parse_rfc3339_weak)
+                    .transpose()
+                    .with_context(|| format!("invalid timestamp for {}", entry.url))?;
+                Ok((sent_at, entry))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // captures aren't always in the order the requests were sent, eg. HAR files list entries
+        // as they complete
+        entries.sort_by_key(|(sent_at, _)| *sent_at);
+
+        let first = entries.iter().find_map(|(sent_at, _)| *sent_at);
+        let entries = entries
+            .into_iter()
+            .map(|(sent_at, entry)| {
+                let offset = match (first, sent_at) {
+                    (Some(first), Some(sent_at)) => {
+                        sent_at.duration_since(first).unwrap_or_default()
+                    }
+                    _ => Duration::ZERO,
+                };
+                Ok(ReplayEntry {
+                    request: entry.into_request()?,
+                    offset,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            entries,
+            preserve_timing: config.preserve_timing,
+        })
+    }
+}
+
+impl JsonlEntry {
+    fn into_request(self) -> Result<RequestConfig> {
+        let Self {
+            method,
+            url,
+            headers,
+            body,
+            ..
+        } = self;
+
+        reqwest::Url::parse(&url).with_context(|| format!("invalid url {url}"))?;
+        // pseudo headers and the content length come from the request itself, and responses
+        // aren't decompressed so don't ask for them encoded
+        let headers = headers
+            .into_iter()
+            .filter(|(name, _)| {
+                !name.starts_with(':')
+                    && !name.eq_ignore_ascii_case("content-length")
+                    && !name.eq_ignore_ascii_case("accept-encoding")
+            })
+            .map(|(name, value)| (name, Template::literal(value)))
+            .collect();
+
+        Ok(RequestConfig {
+            method: parse_method(&method).map_err(|e| anyhow!("{e} for {url}"))?,
+            url: UrlTemplate {
+                base: Template::literal(url),
+                query: vec![],
+                overrides: vec![],
+            },
+            headers,
+            body: body.map(Template::literal),
+        })
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    f64::consts::TAU,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::time::{sleep_until, Duration, Instant, Sleep};
+
+use crate::configs::LoadStage;
+
+/// the longest the scheduler will sleep before re-checking the rate, keeps low rates responsive
+const MAX_WAIT: Duration = Duration::from_millis(10);
+/// how far behind schedule closed model sends can fall (when the concurrency limit is reached)
+/// before the backlog is dropped, the open model keeps every slot
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// a point in the schedule at which a request should be sent
+pub(super) struct Slot {
+    /// the target rate at the time of the slot
+    pub rate: f64,
+    /// when the request was meant to be sent, as an offset from the start of the test
+    pub intended: Duration,
+}
+
+/// issues send slots following a load profile
+pub(super) struct Scheduler {
+    stages: Vec<LoadStage>,
+    start: Instant,
+    sent: f64,
+    /// keep overdue slots instead of dropping the backlog beyond `MAX_LAG`
+    keep_backlog: bool,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl Scheduler {
+    pub fn new(stages: Vec<LoadStage>, start: Instant, keep_backlog: bool) -> Self {
+        Self {
+            stages,
+            start,
+            sent: 0f64,
+            keep_backlog,
+            sleep: Box::pin(sleep_until(start)),
+        }
+    }
+
+    /// wait for the next send slot, returns `None` when the profile is complete
+    pub fn poll_slot(&mut self, cx: &mut Context<'_>) -> Poll<Option<Slot>> {
+        loop {
+            let now = Instant::now();
+            let elapsed = now - self.start;
+            let Some((rate, expected)) = self.at(elapsed.as_secs_f64()) else {
+                return Poll::Ready(None);
+            };
+
+            let lag = rate * MAX_LAG.as_secs_f64() + 1f64;
+            if !self.keep_backlog && expected - self.sent > lag {
+                self.sent = expected - lag;
+            }
+
+            if expected > self.sent {
+                let intended = self.intended(self.sent, elapsed);
+                self.sent += 1f64;
+                return Poll::Ready(Some(Slot {
+                    rate: self
+                        .at(intended.as_secs_f64())
+                        .map_or(rate, |(rate, _)| rate),
+                    intended,
+                }));
+            }
+
+            let wait = match rate > 0f64 {
+                true => Duration::from_secs_f64((self.sent - expected) / rate).min(MAX_WAIT),
+                false => MAX_WAIT,
+            };
+            self.sleep.as_mut().reset(now + wait);
+            if self.sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// when the expected count passed `sent`, which happened before `elapsed`
+    fn intended(&self, sent: f64, elapsed: Duration) -> Duration {
+        let (mut early, mut late) = (0f64, elapsed.as_secs_f64());
+        // to well under a microsecond
+        for _ in 0..40 {
+            let t = (early + late) / 2f64;
+            match self.at(t) {
+                Some((_, expected)) if expected > sent => late = t,
+                _ => early = t,
+            }
+        }
+        Duration::from_secs_f64(late)
+    }
+
+    /// the rate and total number of requests expected `t` seconds into the profile
+    fn at(&self, mut t: f64) -> Option<(f64, f64)> {
+        let mut expected = 0f64;
+        for stage in &self.stages {
+            let length = stage.duration().as_secs_f64();
+            if t < length {
+                return Some((stage.rate_at(t), expected + stage.requests_by(t)));
+            }
+            expected += stage.requests_by(length);
+            t -= length;
+        }
+        None
+    }
+}
+
+impl LoadStage {
+    /// the rate `t` seconds into the stage
+    fn rate_at(&self, t: f64) -> f64 {
+        match *self {
+            Self::Ramp { from, to, duration } => from + (to - from) * t / duration.as_secs_f64(),
+            Self::Hold { rps, .. } => rps,
+            Self::Step {
+                from,
+                to,
+                steps,
+                duration,
+            } => {
+                let step_length = duration.as_secs_f64() / steps.max(1) as f64;
+                step_rate(from, to, steps, (t / step_length).floor() as usize)
+            }
+            Self::Sine {
+                min, max, period, ..
+            } => min + (max - min) * (1f64 - (TAU * t / period.as_secs_f64()).cos()) / 2f64,
+        }
+        .max(0f64)
+    }
+
+    /// the number of requests sent by `t` seconds into the stage
+    fn requests_by(&self, t: f64) -> f64 {
+        match *self {
+            Self::Ramp { from, to, duration } => {
+                from * t + (to - from) * t * t / (2f64 * duration.as_secs_f64())
+            }
+            Self::Hold { rps, .. } => rps * t,
+            Self::Step {
+                from,
+                to,
+                steps,
+                duration,
+            } => {
+                let step_length = duration.as_secs_f64() / steps.max(1) as f64;
+                let complete = (t / step_length).floor() as usize;
+                let full: f64 = (0..complete)
+                    .map(|i| step_rate(from, to, steps, i) * step_length)
+                    .sum();
+                full + step_rate(from, to, steps, complete) * (t - complete as f64 * step_length)
+            }
+            Self::Sine {
+                min, max, period, ..
+            } => {
+                let period = period.as_secs_f64();
+                min * t + (max - min) / 2f64 * (t - period / TAU * (TAU * t / period).sin())
+            }
+        }
+        .max(0f64)
+    }
+}
+
+fn step_rate(from: f64, to: f64, steps: usize, step: usize) -> f64 {
+    match steps {
+        0 | 1 => to,
+        _ => from + (to - from) * step.min(steps - 1) as f64 / (steps - 1) as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn ramp_integral() {
+        let ramp = LoadStage::Ramp {
+            from: 10f64,
+            to: 100f64,
+            duration: secs(1f64),
+        };
+        assert_close(ramp.requests_by(1f64), 55f64);
+        assert_close(ramp.requests_by(0.5f64), 16.25f64);
+        assert_close(ramp.rate_at(0.5f64), 55f64);
+    }
+
+    #[test]
+    fn step_boundaries() {
+        let step = LoadStage::Step {
+            from: 10f64,
+            to: 40f64,
+            steps: 4,
+            duration: secs(4f64),
+        };
+        assert_close(step.rate_at(0.999f64), 10f64);
+        assert_close(step.rate_at(1f64), 20f64);
+        assert_close(step.rate_at(3.5f64), 40f64);
+        assert_close(step.requests_by(1f64), 10f64);
+        assert_close(step.requests_by(1.5f64), 20f64);
+        assert_close(step.requests_by(4f64), 100f64);
+
+        assert_close(step_rate(10f64, 40f64, 4, 0), 10f64);
+        assert_close(step_rate(10f64, 40f64, 4, 3), 40f64);
+        assert_close(step_rate(10f64, 40f64, 4, 7), 40f64);
+        assert_close(step_rate(10f64, 40f64, 1, 0), 40f64);
+        assert_close(step_rate(10f64, 40f64, 0, 0), 40f64);
+    }
+
+    #[test]
+    fn sine_over_a_period() {
+        let sine = LoadStage::Sine {
+            min: 10f64,
+            max: 30f64,
+            period: secs(2f64),
+            duration: secs(4f64),
+        };
+        assert_close(sine.rate_at(0f64), 10f64);
+        assert_close(sine.rate_at(1f64), 30f64);
+        assert_close(sine.requests_by(2f64), 40f64);
+        assert_close(sine.requests_by(4f64), 80f64);
+    }
+
+    #[tokio::test]
+    async fn stages_run_in_order() {
+        let stages = vec![
+            LoadStage::Hold {
+                rps: 10f64,
+                duration: secs(1f64),
+            },
+            LoadStage::Ramp {
+                from: 10f64,
+                to: 30f64,
+                duration: secs(1f64),
+            },
+        ];
+        let scheduler = Scheduler::new(stages, Instant::now(), false);
+        let (rate, expected) = scheduler.at(1.5f64).unwrap();
+        assert_close(rate, 20f64);
+        assert_close(expected, 17.5f64);
+        assert!(scheduler.at(2f64).is_none());
+    }
+
+    #[tokio::test]
+    async fn intended_start() {
+        let hold = vec![LoadStage::Hold {
+            rps: 10f64,
+            duration: secs(1f64),
+        }];
+        let scheduler = Scheduler::new(hold, Instant::now(), false);
+        assert_close(scheduler.intended(4f64, secs(1f64)).as_secs_f64(), 0.4f64);
+
+        // 50t² requests by t
+        let ramp = vec![LoadStage::Ramp {
+            from: 0f64,
+            to: 100f64,
+            duration: secs(1f64),
+        }];
+        let scheduler = Scheduler::new(ramp, Instant::now(), false);
+        assert_close(scheduler.intended(8f64, secs(1f64)).as_secs_f64(), 0.4f64);
+    }
+}
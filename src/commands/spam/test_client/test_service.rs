@@ -1,29 +1,92 @@
 use std::{
     collections::HashMap,
+    sync::Arc,
     task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::{configs::ResolvedConfig, response_info::ResponseInfo};
+use crate::{
+    configs::{BodyAssertion, Extractor, RequestConfig, ResolvedConfig, Step},
+    response_info::{FailureKind, ResponseInfo, Status, StepInfo, Timing},
+    template::TemplateContext,
+};
 use anyhow::Result;
 use futures::future::BoxFuture;
+use jsonschema::Validator;
+use reqwest::header::HeaderMap;
 use tokio::time::{Duration, Instant};
 use tower::{BoxError, Layer, Service};
 
+use super::{
+    assertions::violations,
+    feeder::Row,
+    timing::{record_phases, Phases},
+    transport::TransportError,
+};
 use crate::spam::create_request::build_reqwest;
 
+/// what a `TestRequest` sends
+#[derive(Clone)]
+pub enum Flow {
+    /// the test's `request`, one from its mix or one from its replay
+    Request(RequestConfig),
+    /// every step of the test's scenario, in order
+    Scenario(Arc<Vec<Step>>),
+}
+
+/// a single request for the `TestService` to send
+#[derive(Clone)]
+pub struct TestRequest {
+    pub config: ResolvedConfig,
+    pub flow: Flow,
+    /// the name of the request in a weighted mix
+    pub request_name: Option<String>,
+    /// the index of the request in the test
+    pub seq: usize,
+    /// the feeder row supplying template variables
+    pub vars: Option<Row>,
+    /// the rate the test was targeting when this request was issued
+    pub target_rps: Option<f64>,
+    pub test_start: Instant,
+    /// when the request was meant to be sent, as an offset from `test_start`
+    pub intended_start: Option<Duration>,
+}
+
+impl TestRequest {
+    /// the failure recorded for a request that was never sent because `limit` were in flight
+    pub fn dropped(self, limit: usize) -> ResponseInfo {
+        let reason = format!("{limit} requests already in flight");
+        let mut info =
+            ResponseInfo::error(Duration::ZERO, FailureKind::Dropped, reason, None, None);
+        let offset = self.test_start.elapsed();
+        info.start_offset = offset;
+        info.started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        info.target_rps = self.target_rps;
+        info.request_name = self.request_name;
+        info.intended_start = self.intended_start;
+        info.send_delay = self
+            .intended_start
+            .map(|intended| offset.saturating_sub(intended));
+        info
+    }
+}
+
 #[derive(Clone)]
 pub struct TestService<S> {
     inner: S,
     reqwest_client: reqwest::Client,
+    schema: Option<Arc<Validator>>,
 }
 
-impl<S> Service<ResolvedConfig> for TestService<S>
+impl<S> Service<TestRequest> for TestService<S>
 where
-    S: Service<reqwest::Request, Response = reqwest::Response, Error = reqwest::Error>
+    S: Service<reqwest::Request, Response = reqwest::Response, Error = TransportError>
         + Clone
         + Send
         + 'static,
-    S::Error: Into<BoxError> + ToString + Send + Sync + 'static,
+    S::Error: Into<BoxError> + Send + Sync + 'static,
     S::Future: Send + 'static,
 {
     type Response = ResponseInfo;
@@ -36,70 +99,330 @@ where
 
     fn call(
         &mut self,
-        ResolvedConfig {
-            check_for,
-            collect,
-            latency_header,
-            request,
-            rotate_uuids,
-            ..
-        }: ResolvedConfig,
+        TestRequest {
+            config,
+            flow,
+            request_name,
+            seq,
+            vars,
+            target_rps,
+            test_start,
+            intended_start,
+        }: TestRequest,
     ) -> Self::Future {
-        let req = build_reqwest(&self.reqwest_client, request, rotate_uuids)
-            .build()
-            .unwrap(); // FIXME
-
         let start = Instant::now();
-        let mut inner = self.inner.clone();
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let send_delay = intended_start.map(|intended| {
+            start
+                .saturating_duration_since(test_start)
+                .saturating_sub(intended)
+        });
+        let inner = self.inner.clone();
+        let client = self.reqwest_client.clone();
+        let schema = self.schema.clone();
         Box::pin(async move {
-            let res = inner.call(req).await;
-            let time = start.elapsed();
-
-            let res = match res {
-                Ok(res) => res,
-                Err(e) => {
-                    return Ok(ResponseInfo::error(time, e.to_string(), None, None));
+            let mut info = match flow {
+                Flow::Request(request) => {
+                    let context = TemplateContext {
+                        seq,
+                        vars: vars.as_deref(),
+                    };
+                    let req = build_reqwest(&client, &request, &context);
+                    send(inner, req, &config, schema.as_deref(), false).await.0
+                }
+                Flow::Scenario(steps) => {
+                    let schema = schema.as_deref();
+                    run_scenario(inner, &client, &steps, &config, schema, seq, vars).await
                 }
             };
+            info.start_offset = start.saturating_duration_since(test_start);
+            info.started_at = started_at;
+            info.target_rps = target_rps;
+            info.request_name = request_name;
+            info.intended_start = intended_start;
+            info.send_delay = send_delay;
+            Ok(info)
+        })
+    }
+}
 
-            let collected: HashMap<String, String> = collect
-                .as_ref()
-                .unwrap_or(&vec![])
-                .iter()
-                .map(|v| (v.to_owned(), get_header(&res, v)))
-                .collect();
-
-            let server_latency = latency_header.as_ref().map(|v| {
-                let latency = get_header(&res, v);
-                let latency = latency.parse::<u64>().unwrap_or_default();
-                Duration::from_millis(latency)
-            });
-
-            let Some(items) = check_for else {
-                return Ok(ResponseInfo::success(time, server_latency, collected));
-            };
+/// send a request and check the response, returning its headers and, when read, its body
+async fn send<S>(
+    mut inner: S,
+    req: Result<reqwest::Request>,
+    config: &ResolvedConfig,
+    schema: Option<&Validator>,
+    read_body: bool,
+) -> (ResponseInfo, HeaderMap, Option<String>)
+where
+    S: Service<reqwest::Request, Response = reqwest::Response, Error = TransportError>,
+{
+    let req = match req {
+        Ok(req) => req,
+        Err(e) => {
+            let reason = format!("unable to build request: {e}");
+            let info = ResponseInfo::error(Duration::ZERO, FailureKind::Build, reason, None, None);
+            return (info, HeaderMap::new(), None);
+        }
+    };
+    let start = Instant::now();
+    let (res, phases) = record_phases(inner.call(req)).await;
+    let time = start.elapsed();
 
-            let Ok(text) = res.text().await else {
-                return Ok(ResponseInfo::error(
-                    time,
-                    "text content unavailable from response".into(),
-                    server_latency,
-                    Some(collected),
-                ));
-            };
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            let info = ResponseInfo::error(time, e.kind(), e.to_string(), None, None);
+            return (info, HeaderMap::new(), None);
+        }
+    };
 
-            let unmatched: Vec<_> = items.into_iter().filter(|v| !text.contains(v)).collect();
-            if unmatched.is_empty() {
-                Ok(ResponseInfo::success(time, server_latency, collected))
-            } else {
-                Ok(ResponseInfo::error(
-                    time,
-                    format!("Missing values {unmatched:?}"),
-                    server_latency,
-                    Some(collected),
-                ))
+    let collected: HashMap<String, String> = config
+        .collect
+        .as_ref()
+        .unwrap_or(&vec![])
+        .iter()
+        .map(|v| (v.to_owned(), get_header(&res, v)))
+        .collect();
+
+    let server_latency = config.latency_header.as_ref().map(|v| {
+        let latency = get_header(&res, v);
+        let latency = latency.parse::<u64>().unwrap_or_default();
+        Duration::from_millis(latency)
+    });
+
+    let status = res.status();
+    let headers = res.headers().clone();
+    let mut info = ResponseInfo::success(time, server_latency, collected);
+    info.status_code = Some(status.as_u16());
+    info.http_version = Some(res.version().into());
+    info.content_length = res.content_length();
+    info.timing = Some(match config.phase_timing {
+        true => {
+            let Phases { dns, connect, tls } = phases.unwrap_or_default();
+            let opening = [dns, connect, tls].into_iter().flatten().sum();
+            Timing {
+                dns,
+                connect,
+                tls,
+                ttfb: time.saturating_sub(opening),
+                download: None,
+                phased: true,
             }
-        })
+        }
+        false => Timing {
+            ttfb: time,
+            ..Timing::default()
+        },
+    });
+
+    if !config
+        .expect_status
+        .iter()
+        .any(|r| r.contains(status.as_u16()))
+    {
+        info.fail(FailureKind::Status, format!("Unexpected status {status}"));
+        return (info, headers, None);
+    }
+
+    let mut reasons = vec![];
+    if let Some(assertions) = &config.header_assertions {
+        let failed: Vec<_> = assertions
+            .iter()
+            .filter_map(|a| a.check(&headers).err())
+            .collect();
+        if !failed.is_empty() {
+            reasons.push(format!("Failed header assertions [{}]", failed.join(", ")));
+        }
+    }
+
+    let checks_body = config.check_for.is_some() || config.assertions.is_some() || schema.is_some();
+    if !checks_body && !read_body && !config.drain_body {
+        if !reasons.is_empty() {
+            info.fail(FailureKind::Check, reasons.join("; "));
+        }
+        return (info, headers, None);
+    }
+
+    // the timed transport's timeout only covers the response headers, the body gets what's left of it
+    let remaining = config.timeout.saturating_sub(time);
+    let download_start = Instant::now();
+    let body = tokio::time::timeout(remaining, res.bytes()).await;
+    if let Some(timing) = &mut info.timing {
+        timing.download = Some(download_start.elapsed());
+    }
+    let body = match body {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => {
+            let e = TransportError::Reqwest(e);
+            reasons.push(format!("body unavailable from response: {e}"));
+            info.fail(e.kind(), reasons.join("; "));
+            return (info, headers, None);
+        }
+        Err(_) => {
+            let timeout = config.timeout;
+            reasons.push(format!("body not read within {timeout:?}"));
+            info.fail(FailureKind::Timeout, reasons.join("; "));
+            return (info, headers, None);
+        }
+    };
+    info.body_size = Some(body.len() as u64);
+
+    let text = String::from_utf8_lossy(&body).into_owned();
+    if let Some(items) = &config.check_for {
+        let unmatched: Vec<_> = items.iter().filter(|v| !text.contains(*v)).collect();
+        if !unmatched.is_empty() {
+            reasons.push(format!("Missing values {unmatched:?}"));
+        }
+    }
+    let reads_json = config
+        .assertions
+        .iter()
+        .flatten()
+        .any(BodyAssertion::reads_json);
+    let json: Option<serde_json::Value> = match reads_json || schema.is_some() {
+        true => serde_json::from_str(&text).ok(),
+        false => None,
+    };
+    if let Some(assertions) = &config.assertions {
+        let failed: Vec<_> = assertions
+            .iter()
+            .filter_map(|a| a.check(&text, json.as_ref()).err())
+            .collect();
+        if !failed.is_empty() {
+            reasons.push(format!("Failed assertions [{}]", failed.join(", ")));
+        }
+    }
+    match (schema, &json) {
+        (Some(_), None) => reasons.push("Schema violations [body isn't JSON]".into()),
+        (Some(schema), Some(json)) => {
+            info.violations = violations(schema, json);
+            if !info.violations.is_empty() {
+                let pointers: Vec<_> = info.violations.iter().map(|v| v.location()).collect();
+                reasons.push(format!("Schema violations at [{}]", pointers.join(", ")));
+            }
+        }
+        (None, _) => {}
+    }
+    if !reasons.is_empty() {
+        info.fail(FailureKind::Check, reasons.join("; "));
+    }
+    (info, headers, Some(text))
+}
+
+/// send each step in order, binding the values extracted from each response for later steps
+async fn run_scenario<S>(
+    inner: S,
+    client: &reqwest::Client,
+    steps: &[Step],
+    config: &ResolvedConfig,
+    schema: Option<&Validator>,
+    seq: usize,
+    vars: Option<Row>,
+) -> ResponseInfo
+where
+    S: Service<reqwest::Request, Response = reqwest::Response, Error = TransportError> + Clone,
+{
+    let mut vars: HashMap<String, String> = vars.map(|v| (*v).clone()).unwrap_or_default();
+    let mut timings = vec![];
+    let mut total = Duration::ZERO;
+    let mut server_latency: Option<Duration> = None;
+    let mut collected = HashMap::new();
+    let mut violations = vec![];
+    let mut phase_totals: Option<Timing> = None;
+    let mut last = None;
+
+    for (i, step) in steps.iter().enumerate() {
+        let context = TemplateContext {
+            seq,
+            vars: Some(&vars),
+        };
+        let req = build_reqwest(client, &step.request, &context);
+        let read_body = step.extract.values().any(Extractor::reads_body);
+        let (mut info, headers, body) = send(inner.clone(), req, config, schema, read_body).await;
+
+        total += info.time;
+        if let Some(latency) = info.server_latency {
+            server_latency = Some(server_latency.unwrap_or_default() + latency);
+        }
+        collected.extend(info.collected.drain());
+        violations.append(&mut info.violations);
+        if let Some(step_timing) = info.timing {
+            *phase_totals.get_or_insert_with(Timing::default) += step_timing;
+        }
+        timings.push(StepInfo {
+            name: step.name.clone(),
+            time: info.time,
+            status_code: info.status_code,
+        });
+
+        if info.status.is_success() {
+            let json = body.as_deref().and_then(|b| serde_json::from_str(b).ok());
+            for (name, extractor) in &step.extract {
+                match extract(extractor, &headers, body.as_deref(), json.as_ref()) {
+                    Some(value) => vars.insert(name.clone(), value),
+                    None => {
+                        info.fail(
+                            FailureKind::Check,
+                            format!("nothing extracted for '{name}'"),
+                        );
+                        break;
+                    }
+                };
+            }
+        }
+        if let Status::Failure { reason, .. } = &mut info.status {
+            *reason = format!("step {}: {reason}", step.name);
+        }
+
+        let failed = !info.status.is_success();
+        last = Some(info);
+        if failed {
+            break;
+        }
+        if let Some(think_time) = step.think_time.filter(|_| i + 1 < steps.len()) {
+            tokio::time::sleep(think_time).await;
+        }
+    }
+
+    let mut info = last.unwrap_or_else(|| {
+        ResponseInfo::error(
+            Duration::ZERO,
+            FailureKind::Build,
+            "scenario has no steps".into(),
+            None,
+            None,
+        )
+    });
+    // the scenario takes as long as its steps, without the think time between them
+    info.time = total;
+    info.server_latency = server_latency;
+    info.collected = collected;
+    info.violations = violations;
+    info.timing = phase_totals;
+    info.steps = timings;
+    info
+}
+
+fn extract(
+    extractor: &Extractor,
+    headers: &HeaderMap,
+    body: Option<&str>,
+    json: Option<&serde_json::Value>,
+) -> Option<String> {
+    match extractor {
+        Extractor::Header(name) => Some(headers.get(name)?.to_str().ok()?.to_owned()),
+        Extractor::JsonPath(path) => match path.query(json?).first()? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            value => Some(value.to_string()),
+        },
+        Extractor::Regex(regex) => {
+            let captures = regex.captures(body?)?;
+            let value = captures.get(1).or_else(|| captures.get(0))?;
+            Some(value.as_str().to_owned())
+        }
     }
 }
 
@@ -110,11 +433,11 @@ fn get_header(res: &reqwest::Response, key: &str) -> String {
         .unwrap_or_default()
 }
 
-pub struct TestLayer(reqwest::Client);
+pub struct TestLayer(reqwest::Client, Option<Arc<Validator>>);
 
 impl TestLayer {
-    pub fn new(reqwest_client: reqwest::Client) -> Self {
-        TestLayer(reqwest_client)
+    pub fn new(reqwest_client: reqwest::Client, schema: Option<Arc<Validator>>) -> Self {
+        TestLayer(reqwest_client, schema)
     }
 }
 
@@ -125,6 +448,7 @@ impl<S> Layer<S> for TestLayer {
         TestService {
             inner: service,
             reqwest_client: self.0.clone(),
+            schema: self.1.clone(),
         }
     }
 }
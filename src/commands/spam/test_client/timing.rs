@@ -0,0 +1,37 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::time::Duration;
+
+tokio::task_local! {
+    /// the phases of the connection opened for the request being sent
+    static PHASES: Arc<Mutex<Option<Phases>>>;
+}
+
+/// how long each step of opening a new connection took
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Phases {
+    /// resolving the host, `None` for an ip address
+    pub dns: Option<Duration>,
+    /// the TCP connect
+    pub connect: Option<Duration>,
+    /// the TLS handshake, `None` for http
+    pub tls: Option<Duration>,
+}
+
+/// run a request, returning the phases of the connection it opened or `None` when it was sent on a
+/// pooled connection
+pub(crate) async fn record_phases<F: Future>(request: F) -> (F::Output, Option<Phases>) {
+    let phases = Arc::new(Mutex::new(None));
+    let output = PHASES.scope(phases.clone(), request).await;
+    let phases = *phases.lock().unwrap();
+    (output, phases)
+}
+
+/// record the phases of a connection opened for the request being sent, a connection that finishes
+/// opening in the background after its request took a pooled connection isn't recorded
+pub(super) fn record(phases: Phases) {
+    let _ = PHASES.try_with(|recorded| *recorded.lock().unwrap() = Some(phases));
+}
@@ -0,0 +1,344 @@
+use std::{
+    fmt::Display,
+    io,
+    net::{IpAddr, SocketAddr},
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use hyper::{client::connect::dns::Name, http::uri::Scheme, Body, Client, Uri};
+use hyper_tls::MaybeHttpsStream;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    header::{HeaderValue, ACCEPT},
+};
+use tokio::{
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+use tokio_native_tls::TlsConnector;
+use tower::Service;
+
+use super::timing::{record, Phases};
+use crate::{configs::ResolvedConfig, response_info::FailureKind};
+
+/// sends a test's requests, with reqwest unless the test asks for `phase_timing`
+#[derive(Clone)]
+pub(crate) enum Sender {
+    Reqwest(reqwest::Client),
+    Timed(Transport),
+}
+
+impl Sender {
+    /// the sender for the test, `client` sends its requests unless it times their phases
+    pub fn new(
+        config: &ResolvedConfig,
+        client: &reqwest::Client,
+    ) -> Result<Self, native_tls::Error> {
+        Ok(match config.phase_timing {
+            true => Self::Timed(Transport::new(config.timeout)?),
+            false => Self::Reqwest(client.clone()),
+        })
+    }
+}
+
+impl Service<reqwest::Request> for Sender {
+    type Response = reqwest::Response;
+    type Error = TransportError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: reqwest::Request) -> Self::Future {
+        match self {
+            Self::Reqwest(client) => {
+                let sending = client.execute(request);
+                Box::pin(async move { sending.await.map_err(TransportError::Reqwest) })
+            }
+            Self::Timed(transport) => transport.call(request),
+        }
+    }
+}
+
+/// the system resolver, failing with a `DnsError` so lookup failures can be told apart
+pub(crate) struct Resolver;
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0))
+                .await
+                .map_err(DnsError)?
+                .collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// a failed lookup, so a failure can tell it from other connect errors
+#[derive(Debug)]
+pub(crate) struct DnsError(io::Error);
+
+impl Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for DnsError {}
+
+/// sends requests on connections it opens itself so the TCP connect and TLS handshake can be timed
+/// separately, which reqwest doesn't allow, redirects aren't followed and proxies aren't used
+#[derive(Clone)]
+pub(crate) struct Transport {
+    client: Client<TimedConnector>,
+    /// for the connection and the response headers, the body is read separately
+    timeout: Duration,
+}
+
+impl Transport {
+    pub fn new(timeout: Duration) -> Result<Self, native_tls::Error> {
+        let connector = TimedConnector {
+            tls: native_tls::TlsConnector::new()?.into(),
+        };
+        let client = Client::builder()
+            .pool_idle_timeout(Duration::from_secs(10))
+            .build(connector);
+        Ok(Self { client, timeout })
+    }
+}
+
+impl Service<reqwest::Request> for Transport {
+    type Response = reqwest::Response;
+    type Error = TransportError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: reqwest::Request) -> Self::Future {
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        Box::pin(async move {
+            let request = to_hyper(request)?;
+            let response = tokio::time::timeout(timeout, client.request(request))
+                .await
+                .map_err(|_| TransportError::Timeout(timeout))?
+                .map_err(TransportError::Http)?;
+            Ok(reqwest::Response::from(response))
+        })
+    }
+}
+
+/// the request as hyper sends it, with the `accept` header reqwest would have added
+fn to_hyper(request: reqwest::Request) -> Result<hyper::Request<Body>, TransportError> {
+    let (mut parts, body) = hyper::Request::<reqwest::Body>::try_from(request)
+        .map_err(|e| TransportError::Build(e.to_string()))?
+        .into_parts();
+    // requests are built from templates, so their bodies are never streamed
+    let body = body
+        .as_bytes()
+        .map(|bytes| Body::from(bytes.to_vec()))
+        .ok_or_else(|| TransportError::Build("streamed bodies aren't supported".into()))?;
+    parts
+        .headers
+        .entry(ACCEPT)
+        .or_insert(HeaderValue::from_static("*/*"));
+    Ok(hyper::Request::from_parts(parts, body))
+}
+
+/// why a request wasn't sent or its response headers weren't received
+#[derive(Debug)]
+pub(crate) enum TransportError {
+    Build(String),
+    Timeout(Duration),
+    Http(hyper::Error),
+    Reqwest(reqwest::Error),
+}
+
+impl TransportError {
+    /// the kind of failure, using the step of opening a connection that failed
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            Self::Build(_) => FailureKind::Build,
+            Self::Timeout(_) => FailureKind::Timeout,
+            Self::Http(e) => match std::error::Error::source(e).and_then(|c| c.downcast_ref()) {
+                Some(ConnectError::Dns(_)) => FailureKind::Dns,
+                Some(ConnectError::Connect(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    FailureKind::ConnectionRefused
+                }
+                Some(ConnectError::Tls(_)) => FailureKind::Tls,
+                Some(ConnectError::InvalidUri(_) | ConnectError::Connect(_)) => {
+                    FailureKind::Connect
+                }
+                None if e.is_connect() => FailureKind::Connect,
+                None => FailureKind::Request,
+            },
+            Self::Reqwest(e) => reqwest_failure_kind(e),
+        }
+    }
+}
+
+/// classify a reqwest error, looking through its causes for what reqwest doesn't tell apart
+fn reqwest_failure_kind(e: &reqwest::Error) -> FailureKind {
+    let mut causes = std::iter::successors(std::error::Error::source(e), |c| c.source());
+    if e.is_timeout() {
+        FailureKind::Timeout
+    } else if e.is_connect() {
+        let kind = causes.find_map(|cause| {
+            if cause.is::<DnsError>() {
+                Some(FailureKind::Dns)
+            } else if cause.is::<native_tls::Error>() || mentions_tls(cause) {
+                Some(FailureKind::Tls)
+            } else {
+                match cause.downcast_ref::<io::Error>()?.kind() {
+                    io::ErrorKind::ConnectionRefused => Some(FailureKind::ConnectionRefused),
+                    _ => None,
+                }
+            }
+        });
+        kind.unwrap_or(FailureKind::Connect)
+    } else if e.is_decode() {
+        FailureKind::Decode
+    } else if e.is_body() {
+        FailureKind::Body
+    } else if e.is_redirect() {
+        FailureKind::Redirect
+    } else if e.is_builder() {
+        FailureKind::Build
+    } else if e.is_request() {
+        FailureKind::Request
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// the downcast above only sees reqwest's TLS errors while this crate and reqwest share a native-tls
+/// version (and reqwest uses native-tls rather than rustls), so fall back on the error's message
+fn mentions_tls(cause: &(dyn std::error::Error + 'static)) -> bool {
+    let message = cause.to_string().to_lowercase();
+    ["tls", "ssl", "certificate", "handshake"]
+        .iter()
+        .any(|word| message.contains(word))
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Build(e) => write!(f, "unable to build request: {e}"),
+            Self::Timeout(timeout) => write!(f, "no response within {timeout:?}"),
+            // hyper and reqwest include the cause
+            Self::Http(e) => e.fmt(f),
+            Self::Reqwest(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            Self::Reqwest(e) => Some(e),
+            Self::Build(_) | Self::Timeout(_) => None,
+        }
+    }
+}
+
+/// the step of opening a connection that failed
+#[derive(Debug)]
+pub(crate) enum ConnectError {
+    InvalidUri(Uri),
+    Dns(io::Error),
+    Connect(io::Error),
+    Tls(native_tls::Error),
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUri(uri) => write!(f, "no host in {uri}"),
+            Self::Dns(e) => write!(f, "dns error: {e}"),
+            Self::Connect(e) => write!(f, "tcp connect error: {e}"),
+            Self::Tls(e) => write!(f, "tls handshake error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/// opens connections for the `Transport`, timing the lookup, connect and handshake
+#[derive(Clone)]
+pub(crate) struct TimedConnector {
+    tls: TlsConnector,
+}
+
+impl Service<Uri> for TimedConnector {
+    type Response = MaybeHttpsStream<TcpStream>;
+    type Error = ConnectError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let tls = self.tls.clone();
+        Box::pin(async move {
+            let https = uri.scheme() == Some(&Scheme::HTTPS);
+            let host = match uri.host() {
+                Some(host) => host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_owned(),
+                None => return Err(ConnectError::InvalidUri(uri)),
+            };
+            let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+            let mut phases = Phases::default();
+
+            let addrs: Vec<_> = match host.parse::<IpAddr>() {
+                Ok(ip) => vec![SocketAddr::new(ip, port)],
+                Err(_) => {
+                    let start = Instant::now();
+                    let addrs = tokio::net::lookup_host((host.as_str(), port))
+                        .await
+                        .map_err(ConnectError::Dns)?
+                        .collect();
+                    phases.dns = Some(start.elapsed());
+                    addrs
+                }
+            };
+
+            let start = Instant::now();
+            let tcp = connect(&addrs).await.map_err(ConnectError::Connect)?;
+            phases.connect = Some(start.elapsed());
+            if !https {
+                record(phases);
+                return Ok(MaybeHttpsStream::Http(tcp));
+            }
+
+            let start = Instant::now();
+            let stream = tls.connect(&host, tcp).await.map_err(ConnectError::Tls)?;
+            phases.tls = Some(start.elapsed());
+            record(phases);
+            Ok(MaybeHttpsStream::Https(stream))
+        })
+    }
+}
+
+/// connect to the first of the addresses that accepts
+async fn connect(addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut error = io::Error::new(io::ErrorKind::NotFound, "the host has no addresses");
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
@@ -0,0 +1,172 @@
+use std::{fmt::Write as _, fs, path::Path};
+
+use anyhow::Result;
+use tokio::time::Duration;
+
+use crate::{configs::Thresholds, TestSummary};
+
+/// the outcome of checking a single threshold
+pub(crate) struct Check {
+    pub name: &'static str,
+    pub expected: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+/// the threshold checks for a test
+pub(crate) struct Evaluation {
+    pub test: String,
+    pub time: Duration,
+    pub checks: Vec<Check>,
+    /// why the test couldn't run, so nothing was checked
+    pub error: Option<String>,
+}
+
+impl Thresholds {
+    pub(super) fn evaluate(&self, result: &TestSummary) -> Evaluation {
+        let mut checks = vec![];
+        let mut max_latency = |name, max: Option<Duration>, ratio| {
+            if let Some(max) = max {
+                let actual = result.percentile(ratio);
+                checks.push(Check {
+                    name,
+                    expected: format!("<= {max:?}"),
+                    actual: actual.map_or("n/a".into(), |a| format!("{a:?}")),
+                    passed: actual.is_some_and(|a| a <= max),
+                });
+            }
+        };
+        max_latency("p95 latency", self.max_p95, 0.95);
+        max_latency("p99 latency", self.max_p99, 0.99);
+
+        if let Some(min) = self.min_success_rate {
+            let actual = result.success_rate();
+            checks.push(Check {
+                name: "success rate",
+                expected: format!(">= {min}%"),
+                actual: actual.map_or("n/a".into(), |a| format!("{a:.2}%")),
+                passed: actual.is_some_and(|a| a >= min),
+            });
+        }
+        if let Some(min) = self.min_rps {
+            let actual = result.rps();
+            checks.push(Check {
+                name: "rps",
+                expected: format!(">= {min}"),
+                actual: format!("{actual:.1}"),
+                passed: actual >= min,
+            });
+        }
+
+        Evaluation {
+            test: result.name.clone(),
+            time: result.total_time(),
+            checks,
+            error: None,
+        }
+    }
+}
+
+impl Evaluation {
+    /// a test with thresholds that failed to run
+    pub fn errored(test: String, error: String) -> Self {
+        Self {
+            test,
+            time: Duration::ZERO,
+            checks: vec![],
+            error: Some(error),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.checks.iter().all(|c| c.passed)
+    }
+
+    pub fn table(&self) -> String {
+        let mut table = String::from("    thresholds:\n");
+        for check in &self.checks {
+            let _ = writeln!(
+                table,
+                "      {}  {:<14} {:>16} (expected {})",
+                match check.passed {
+                    true => "PASS",
+                    false => "FAIL",
+                },
+                check.name,
+                check.actual,
+                check.expected
+            );
+        }
+        table
+    }
+}
+
+/// write the threshold checks as a JUnit report, one test case per check and an erroring test
+/// case for each test that couldn't run
+pub(super) fn write_junit<P: AsRef<Path>>(
+    path: P,
+    suite: &str,
+    evaluations: &[Evaluation],
+) -> Result<()> {
+    let errors = evaluations.iter().filter(|e| e.error.is_some()).count();
+    let tests = evaluations.iter().map(|e| e.checks.len()).sum::<usize>() + errors;
+    let failures = evaluations
+        .iter()
+        .flat_map(|e| &e.checks)
+        .filter(|c| !c.passed)
+        .count();
+    let time: f64 = evaluations.iter().map(|e| e.time.as_secs_f64()).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.3}\">",
+        escape(suite)
+    );
+    for evaluation in evaluations {
+        if let Some(error) = &evaluation.error {
+            let _ = writeln!(
+                xml,
+                "  <testcase classname=\"{}\" name=\"run\" time=\"0.000\">\n    <error message=\"{}\"/>\n  </testcase>",
+                escape(&evaluation.test),
+                escape(error)
+            );
+        }
+        for check in &evaluation.checks {
+            let _ = write!(
+                xml,
+                "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(&evaluation.test),
+                check.name,
+                evaluation.time.as_secs_f64()
+            );
+            match check.passed {
+                true => xml += "/>\n",
+                false => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <failure message=\"{} was {} (expected {})\"/>\n  </testcase>",
+                        check.name,
+                        escape(&check.actual),
+                        escape(&check.expected)
+                    );
+                }
+            }
+        }
+    }
+    xml += "</testsuite>\n";
+
+    if let Some(parent) = path.as_ref().parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    Ok(fs::write(path, xml)?)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
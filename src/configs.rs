@@ -1,6 +1,9 @@
-use reqwest::Url;
+use crate::template::Template;
+use regex::Regex;
+use reqwest::{Method, Url};
 use serde::{de, Deserialize, Deserializer};
-use std::{collections::HashMap, time::Duration};
+use serde_json_path::JsonPath;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 mod defaults {
     use std::time::Duration;
@@ -11,9 +14,30 @@ mod defaults {
     pub fn rotate_uuids() -> bool {
         false
     }
+    pub fn open_model() -> bool {
+        false
+    }
+    pub fn max_in_flight() -> usize {
+        10_000
+    }
+    pub fn parallel() -> bool {
+        false
+    }
+    pub fn drain_body() -> bool {
+        false
+    }
+    pub fn phase_timing() -> bool {
+        false
+    }
     pub fn timeout() -> Duration {
         Duration::from_secs(30)
     }
+    pub fn weight() -> u32 {
+        1
+    }
+    pub fn expect_status() -> Vec<super::StatusRange> {
+        vec![super::StatusRange::class(2)]
+    }
 }
 #[derive(Debug, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
@@ -21,10 +45,20 @@ pub struct GlobalConfig {
     /// values to check for in all responses
     pub check_for: Option<Vec<String>>,
 
+    /// checks on the body of all responses
+    pub assertions: Option<Vec<BodyAssertion>>,
+
+    /// checks on the headers of all responses
+    pub header_assertions: Option<Vec<HeaderAssertion>>,
+
     /// the number of requests unless specified in a specific test config
     #[serde(default = "defaults::count")]
     pub count: usize,
 
+    /// how long to run each test for unless specified in a specific test config, takes precedence over `count`
+    #[serde(deserialize_with = "deserialize::duration_option", default)]
+    pub duration: Option<Duration>,
+
     /// whether to use a random uuid in the `user` request param for each request
     #[serde(default = "defaults::rotate_uuids")]
     pub rotate_uuids: bool,
@@ -39,10 +73,38 @@ pub struct GlobalConfig {
     )]
     pub timeout: Duration,
 
+    /// status codes counted as successes, eg. `200`, `"2xx"`, `"200-299"` or a list of those
+    #[serde(
+        default = "defaults::expect_status",
+        deserialize_with = "deserialize::status_ranges"
+    )]
+    pub expect_status: Vec<StatusRange>,
+
     /// max rps default for each test
     pub max_rps: usize,
 
     pub max_concurrent: usize,
+
+    /// send requests on schedule regardless of how many are in flight (ignores `max_concurrent`)
+    #[serde(default = "defaults::open_model")]
+    pub open_model: bool,
+
+    /// the most open model requests in flight at once, slots beyond it are dropped and counted as failures
+    #[serde(default = "defaults::max_in_flight")]
+    pub max_in_flight: usize,
+
+    /// run all the selected tests at the same time, each with its own limits
+    #[serde(default = "defaults::parallel")]
+    pub parallel: bool,
+
+    /// read every response body, even when nothing checks it, so the total latency includes the download
+    #[serde(default = "defaults::drain_body")]
+    pub drain_body: bool,
+
+    /// send requests on connections spam-can opens itself, to time the dns lookup, TCP connect and TLS
+    /// handshake separately, these don't follow redirects or use proxies
+    #[serde(default = "defaults::phase_timing")]
+    pub phase_timing: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -57,11 +119,27 @@ pub struct SpamConfig {
 #[serde(deny_unknown_fields)]
 pub struct TestConfig {
     pub name: String,
-    pub request: RequestConfig,
+    /// the request to send, required unless the test has `requests`, a `scenario` or a `replay`
+    pub request: Option<RequestConfig>,
+    /// named requests sent in proportion to their weights, sharing the test's limits
+    pub requests: Option<Vec<WeightedRequest>>,
+    /// steps sent in order for each iteration, `max_rps` and `max_concurrent` limit iterations
+    pub scenario: Option<Vec<Step>>,
+    /// send the requests from a capture file instead of `request`
+    pub replay: Option<ReplayConfig>,
     /// items to check for in the request text
     pub check_for: Option<Vec<String>>,
+    /// checks on the response body, added to the global assertions
+    pub assertions: Option<Vec<BodyAssertion>>,
+    /// checks on the response headers, added to the global header assertions
+    pub header_assertions: Option<Vec<HeaderAssertion>>,
+    /// a JSON Schema file every response body is validated against
+    pub schema: Option<PathBuf>,
     /// override for the number of requests to this url
     pub count: Option<usize>,
+    /// override for how long to run this test for, takes precedence over `count`
+    #[serde(deserialize_with = "deserialize::duration_option", default)]
+    pub duration: Option<Duration>,
     /// whether to use a random uuid in the `user` request param for each request
     pub rotate_uuids: Option<bool>,
     /// header values to collect from responses
@@ -71,58 +149,403 @@ pub struct TestConfig {
     /// timeout for all requests
     #[serde(deserialize_with = "deserialize::duration_option", default)]
     pub timeout: Option<Duration>,
+    /// override for the status codes counted as successes
+    #[serde(deserialize_with = "deserialize::status_ranges_option", default)]
+    pub expect_status: Option<Vec<StatusRange>>,
     pub max_rps: Option<usize>,
     pub max_concurrent: Option<usize>,
+    /// override for sending requests on schedule regardless of how many are in flight
+    pub open_model: Option<bool>,
+    /// override for the most open model requests in flight at once
+    pub max_in_flight: Option<usize>,
+    /// override for reading every response body
+    pub drain_body: Option<bool>,
+    /// override for timing the phases of opening connections
+    pub phase_timing: Option<bool>,
+    /// stages to vary the request rate over the test, replaces `max_rps`, `count` and `duration`
+    pub load_profile: Option<Vec<LoadStage>>,
+    /// limits the results must meet for the test to pass
+    pub thresholds: Option<Thresholds>,
+    /// file supplying template variables for each request
+    pub feeder: Option<FeederConfig>,
 }
 
 #[derive(Clone)]
 pub(crate) struct ResolvedConfig {
     pub name: String,
-    pub request: RequestConfig,
+    pub request: Option<RequestConfig>,
+    pub requests: Option<Vec<WeightedRequest>>,
+    pub scenario: Option<Vec<Step>>,
+    pub replay: Option<ReplayConfig>,
     pub check_for: Option<Vec<String>>,
+    pub assertions: Option<Vec<BodyAssertion>>,
+    pub header_assertions: Option<Vec<HeaderAssertion>>,
+    pub schema: Option<PathBuf>,
     pub count: usize,
-    pub rotate_uuids: bool,
+    pub duration: Option<Duration>,
     pub collect: Option<Vec<String>>,
     pub latency_header: Option<String>,
     pub timeout: Duration,
+    pub expect_status: Vec<StatusRange>,
     pub max_rps: usize,
     pub max_concurrent: usize,
+    pub open_model: bool,
+    pub max_in_flight: usize,
+    pub drain_body: bool,
+    pub phase_timing: bool,
+    pub load_profile: Option<Vec<LoadStage>>,
+    pub thresholds: Option<Thresholds>,
+    pub feeder: Option<FeederConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FeederConfig {
+    /// a `.csv` file with a header row or a `.jsonl` file of objects, each column or key is a template variable
+    pub path: PathBuf,
+    #[serde(default)]
+    pub strategy: FeederStrategy,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WeightedRequest {
+    /// tags the results of this request
+    pub name: String,
+    /// share of the test's requests relative to the other weights
+    #[serde(default = "defaults::weight")]
+    pub weight: u32,
+    pub request: RequestConfig,
+}
+
+/// a request in a scenario, later steps can use the variables extracted from earlier responses
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub name: String,
+    pub request: RequestConfig,
+    /// variables to bind from the response, eg. `{ token = { jsonpath = "$.token" } }`
+    #[serde(default)]
+    pub extract: HashMap<String, Extractor>,
+    /// how long to wait after this step before sending the next
+    #[serde(deserialize_with = "deserialize::duration_option", default)]
+    pub think_time: Option<Duration>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Extractor {
+    /// the first value matched by a JSONPath query on the body
+    JsonPath(JsonPath),
+    /// the first capture group, or the whole match, of a regex on the body
+    Regex(#[serde(deserialize_with = "deserialize::regex")] Regex),
+    /// the value of a response header
+    Header(String),
+}
+
+impl Extractor {
+    /// whether the extractor needs the response body
+    pub fn reads_body(&self) -> bool {
+        !matches!(self, Self::Header(_))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// a `.har` file or a `.jsonl` file of `{ method, url, headers, body, timestamp }` objects
+    pub path: PathBuf,
+    /// send each request at the same offset from the start as in the capture
+    #[serde(default)]
+    pub preserve_timing: bool,
+}
+
+/// how rows are picked from a feeder for each request
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FeederStrategy {
+    /// in order, starting again from the first row when exhausted
+    #[default]
+    Circular,
+    /// in order, a row for each request: a test with more `count` than rows fails to start, one with a
+    /// `duration` warns and reuses the last row once they run out
+    Sequential,
+    /// in order, the test stops when the rows are exhausted even if it has `count` or `duration` left
+    StopWhenExhausted,
+    /// a random row for each request
+    Random,
+}
+
+/// service level objectives checked after a test completes, a breach fails the `spam` command
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    /// maximum p95 latency of the successful requests
+    #[serde(deserialize_with = "deserialize::duration_option", default)]
+    pub max_p95: Option<Duration>,
+    /// maximum p99 latency of the successful requests
+    #[serde(deserialize_with = "deserialize::duration_option", default)]
+    pub max_p99: Option<Duration>,
+    /// minimum percentage of successful requests (0-100)
+    pub min_success_rate: Option<f64>,
+    /// minimum achieved requests per second
+    pub min_rps: Option<f64>,
+}
+
+/// a check on the response body, JSONPath assertions fail when the body isn't JSON
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BodyAssertion {
+    /// the query matches at least one value
+    Exists { path: JsonPath },
+    /// the first value matched equals `value`
+    Equals {
+        path: JsonPath,
+        value: serde_json::Value,
+    },
+    /// the number of values matched, or the length of a single matched array, is within `min` and `max`
+    Count {
+        path: JsonPath,
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// the first value matched is a number that compares to `value` with `op`, eg. `">="`
+    Compare {
+        path: JsonPath,
+        op: Comparison,
+        value: f64,
+    },
+    /// the body matches the regex
+    Regex {
+        #[serde(deserialize_with = "deserialize::regex")]
+        pattern: Regex,
+    },
+    /// the body doesn't contain `value`
+    NotContains { value: String },
 }
 
+/// a check on a response header, header names are case insensitive
 #[derive(Debug, Deserialize, Clone)]
-#[serde(tag = "method", deny_unknown_fields)]
-pub enum RequestConfig {
-    #[serde(alias = "get", alias = "GET")]
-    Get {
-        #[serde(deserialize_with = "deserialize::url")]
-        url: Url,
-        #[serde(default)]
-        headers: HashMap<String, String>,
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum HeaderAssertion {
+    /// the header is present
+    Exists { header: String },
+    /// the header is present with exactly `value`
+    Equals { header: String, value: String },
+    /// the header is present and matches the regex
+    Regex {
+        header: String,
+        #[serde(deserialize_with = "deserialize::regex")]
+        pattern: Regex,
     },
-    #[serde(alias = "post", alias = "POST")]
-    Post {
-        #[serde(deserialize_with = "deserialize::url")]
-        url: Url,
-        #[serde(default)]
-        headers: HashMap<String, String>,
-        #[serde(default)]
-        body: String,
+    /// the header isn't present
+    Absent { header: String },
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum Comparison {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = ">")]
+    Gt,
+}
+
+/// a stage of a load profile, stages are run in order
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum LoadStage {
+    /// linearly change the rate from `from` to `to` rps
+    Ramp {
+        from: f64,
+        to: f64,
+        #[serde(deserialize_with = "deserialize::duration")]
+        duration: Duration,
     },
+    /// hold a constant rate (`spike` is an alias for a short hold at a high rate)
+    #[serde(alias = "spike")]
+    Hold {
+        rps: f64,
+        #[serde(deserialize_with = "deserialize::duration")]
+        duration: Duration,
+    },
+    /// change the rate from `from` to `to` in `steps` equal increments
+    Step {
+        from: f64,
+        to: f64,
+        steps: usize,
+        #[serde(deserialize_with = "deserialize::duration")]
+        duration: Duration,
+    },
+    /// oscillate between `min` and `max` rps, starting at `min`
+    Sine {
+        min: f64,
+        max: f64,
+        #[serde(deserialize_with = "deserialize::duration")]
+        period: Duration,
+        #[serde(deserialize_with = "deserialize::duration")]
+        duration: Duration,
+    },
+}
+
+impl LoadStage {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Ramp { duration, .. }
+            | Self::Hold { duration, .. }
+            | Self::Step { duration, .. }
+            | Self::Sine { duration, .. } => *duration,
+        }
+    }
+}
+
+/// an inclusive range of http status codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl StatusRange {
+    /// all codes in a class, eg. `class(2)` is `2xx`
+    pub fn class(class: u16) -> Self {
+        Self {
+            min: class * 100,
+            max: class * 100 + 99,
+        }
+    }
+
+    pub fn contains(&self, code: u16) -> bool {
+        (self.min..=self.max).contains(&code)
+    }
+}
+
+impl std::str::FromStr for StatusRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let code = |v: &str| {
+            v.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|c| (100..=999).contains(c))
+                .ok_or_else(|| format!("invalid status code '{v}'"))
+        };
+
+        if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+            return match class.parse::<u16>() {
+                Ok(class) if (1..=9).contains(&class) => Ok(Self::class(class)),
+                _ => Err(format!("invalid status class '{s}'")),
+            };
+        }
+
+        match s.split_once('-') {
+            Some((min, max)) => {
+                let (min, max) = (code(min)?, code(max)?);
+                if min > max {
+                    return Err(format!("invalid status range '{s}'"));
+                }
+                Ok(Self { min, max })
+            }
+            None => code(s).map(|c| Self { min: c, max: c }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RequestConfig {
+    /// standard methods are case insensitive (eg. `get`, `Get` or `GET`), other methods are sent as written
+    #[serde(deserialize_with = "deserialize::method")]
+    pub method: Method,
+    #[serde(deserialize_with = "deserialize::url")]
+    pub url: UrlTemplate,
+    /// header values can contain template placeholders
+    #[serde(default)]
+    pub headers: HashMap<String, Template>,
+    /// sent with any method when set, `POST`, `PUT` and `PATCH` requests send an empty body otherwise
+    pub body: Option<Template>,
+}
+
+/// standard methods are case insensitive, other methods are kept as written
+pub(crate) fn parse_method(method: &str) -> Result<Method, String> {
+    let upper = method.to_ascii_uppercase();
+    let standard = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::HEAD,
+        Method::OPTIONS,
+        Method::CONNECT,
+        Method::TRACE,
+    ];
+    match standard.into_iter().find(|m| m.as_str() == upper) {
+        Some(method) => Ok(method),
+        None => Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string()),
+    }
+}
+
+impl RequestConfig {
+    /// names of the template variables used by the request
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        let url = &self.url;
+        url.base
+            .variables()
+            .chain(url.query.iter().flat_map(|(_, v)| v.variables()))
+            .chain(url.overrides.iter().flat_map(|(_, v)| v.variables()))
+            .chain(self.headers.values().flat_map(Template::variables))
+            .chain(self.body.iter().flat_map(Template::variables))
+    }
+}
+
+/// a url whose base and query values can contain template placeholders
+#[derive(Debug, Clone)]
+pub struct UrlTemplate {
+    pub base: Template,
+    /// query parameters appended to the base url
+    pub query: Vec<(String, Template)>,
+    /// query parameters that replace any existing parameter with the same name
+    pub overrides: Vec<(String, Template)>,
 }
 
 impl TestConfig {
     pub(crate) fn resolve(self, global: &GlobalConfig) -> ResolvedConfig {
         let Self {
             name,
-            request,
+            mut request,
+            mut requests,
+            mut scenario,
+            replay,
             check_for,
+            assertions,
+            header_assertions,
+            schema,
             count,
+            duration,
             rotate_uuids,
             collect,
             latency_header,
             timeout,
+            expect_status,
             max_rps,
             max_concurrent,
+            open_model,
+            max_in_flight,
+            drain_body,
+            phase_timing,
+            load_profile,
+            thresholds,
+            feeder,
         } = self;
 
         let check_for = match (check_for, &global.check_for) {
@@ -132,29 +555,85 @@ impl TestConfig {
             (x, None) => x,
         };
 
+        let assertions = match (assertions, &global.assertions) {
+            (Some(l), Some(g)) => Some([l, g.clone()].concat()),
+            (None, None) => None,
+            (None, x) => x.clone(),
+            (x, None) => x,
+        };
+
+        let header_assertions = match (header_assertions, &global.header_assertions) {
+            (Some(l), Some(g)) => Some([l, g.clone()].concat()),
+            (None, None) => None,
+            (None, x) => x.clone(),
+            (x, None) => x,
+        };
+
         let collect = match (collect, &global.collect) {
             (Some(l), Some(g)) => Some([l, g.clone()].concat()),
             (None, None) => None,
             (None, x) => x.clone(),
             (x, None) => x,
         };
-        let count = count.unwrap_or(global.count);
-        let rotate_uuids = rotate_uuids.unwrap_or(global.rotate_uuids);
+        // a count on the test overrides a global duration
+        let duration = match (duration, count) {
+            (Some(d), _) => Some(d),
+            (None, Some(_)) => None,
+            (None, None) => global.duration,
+        };
+        // a load profile runs for exactly as long as its stages
+        let duration = match &load_profile {
+            Some(stages) => Some(stages.iter().map(LoadStage::duration).sum()),
+            None => duration,
+        };
+        // a replay sends the whole capture unless the test limits it
+        let count = match (&replay, count) {
+            (_, Some(count)) => count,
+            (Some(_), None) => usize::MAX,
+            (None, None) => global.count,
+        };
+        if rotate_uuids.unwrap_or(global.rotate_uuids) {
+            let user = Template::parse("m-{{uuid simple}}").expect("valid template");
+            let mixed = requests.iter_mut().flatten().map(|r| &mut r.request);
+            let steps = scenario.iter_mut().flatten().map(|s| &mut s.request);
+            for request in request.iter_mut().chain(mixed).chain(steps) {
+                request.url.overrides.push(("user".into(), user.clone()));
+            }
+        }
         let timeout = timeout.unwrap_or(global.timeout);
+        let expect_status = expect_status.unwrap_or_else(|| global.expect_status.clone());
         let max_rps = max_rps.unwrap_or(global.max_rps);
         let max_concurrent = max_concurrent.unwrap_or(global.max_concurrent);
+        let open_model = open_model.unwrap_or(global.open_model);
+        let max_in_flight = max_in_flight.unwrap_or(global.max_in_flight);
+        let drain_body = drain_body.unwrap_or(global.drain_body);
+        let phase_timing = phase_timing.unwrap_or(global.phase_timing);
 
         ResolvedConfig {
             name,
             request,
+            requests,
+            scenario,
+            replay,
             check_for,
+            assertions,
+            header_assertions,
+            schema,
             count,
-            rotate_uuids,
+            duration,
             collect,
             latency_header,
             timeout,
+            expect_status,
             max_rps,
             max_concurrent,
+            open_model,
+            max_in_flight,
+            drain_body,
+            phase_timing,
+            load_profile,
+            thresholds,
+            feeder,
         }
     }
 }
@@ -164,25 +643,43 @@ mod deserialize {
 
     #[derive(Deserialize)]
     struct UrlComponents {
-        base_url: String,
-        query: Option<HashMap<String, String>>,
+        base_url: Template,
+        query: Option<HashMap<String, Template>>,
     }
 
     #[derive(Deserialize)]
     #[serde(untagged)]
     enum UrlOrParts {
-        Url(Url),
+        Url(Template),
         Parts(UrlComponents),
     }
-    pub fn url<'de, D: Deserializer<'de>>(d: D) -> Result<Url, D::Error> {
-        match UrlOrParts::deserialize(d)? {
-            UrlOrParts::Url(url) => Ok(url),
-            UrlOrParts::Parts(parts) => match parts.query {
-                Some(query) => Url::parse_with_params(&parts.base_url, query.iter()),
-                None => Url::parse(&parts.base_url),
-            }
-            .map_err(de::Error::custom),
+    pub fn url<'de, D: Deserializer<'de>>(d: D) -> Result<UrlTemplate, D::Error> {
+        let (base, query) = match UrlOrParts::deserialize(d)? {
+            UrlOrParts::Url(url) => (url, vec![]),
+            UrlOrParts::Parts(parts) => (
+                parts.base_url,
+                parts.query.unwrap_or_default().into_iter().collect(),
+            ),
+        };
+        // templated urls can only be checked once rendered
+        if base.is_literal() {
+            Url::parse(&base.to_string()).map_err(de::Error::custom)?;
         }
+        Ok(UrlTemplate {
+            base,
+            query,
+            overrides: vec![],
+        })
+    }
+
+    pub fn regex<'de, D: Deserializer<'de>>(d: D) -> Result<Regex, D::Error> {
+        let regex = String::deserialize(d)?;
+        Regex::new(&regex).map_err(de::Error::custom)
+    }
+
+    pub fn method<'de, D: Deserializer<'de>>(d: D) -> Result<Method, D::Error> {
+        let method = String::deserialize(d)?;
+        super::parse_method(&method).map_err(de::Error::custom)
     }
 
     #[derive(Deserialize)]
@@ -205,4 +702,94 @@ mod deserialize {
             None => Ok(None),
         }
     }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StatusOrRange {
+        Code(u16),
+        Range(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    fn to_status_range<E: de::Error>(value: StatusOrRange) -> Result<StatusRange, E> {
+        match value {
+            StatusOrRange::Code(code) => code.to_string().parse(),
+            StatusOrRange::Range(range) => range.parse(),
+        }
+        .map_err(de::Error::custom)
+    }
+
+    pub fn status_ranges<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<StatusRange>, D::Error> {
+        match OneOrMany::<StatusOrRange>::deserialize(d)? {
+            OneOrMany::One(value) => Ok(vec![to_status_range(value)?]),
+            OneOrMany::Many(values) => values.into_iter().map(to_status_range).collect(),
+        }
+    }
+    pub fn status_ranges_option<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<Vec<StatusRange>>, D::Error> {
+        match Option::<OneOrMany<StatusOrRange>>::deserialize(d)? {
+            Some(OneOrMany::One(value)) => Ok(Some(vec![to_status_range(value)?])),
+            Some(OneOrMany::Many(values)) => values
+                .into_iter()
+                .map(to_status_range)
+                .collect::<Result<_, _>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: u16, max: u16) -> Result<StatusRange, String> {
+        Ok(StatusRange { min, max })
+    }
+
+    #[test]
+    fn status_range_single_code() {
+        assert_eq!("200".parse(), range(200, 200));
+        assert_eq!(" 404 ".parse(), range(404, 404));
+    }
+
+    #[test]
+    fn status_range_class() {
+        assert_eq!("2xx".parse(), range(200, 299));
+        assert_eq!("5XX".parse(), range(500, 599));
+        assert!("0xx".parse::<StatusRange>().is_err());
+        assert!("10xx".parse::<StatusRange>().is_err());
+        assert!("axx".parse::<StatusRange>().is_err());
+    }
+
+    #[test]
+    fn status_range_span() {
+        assert_eq!("200-299".parse(), range(200, 299));
+        assert_eq!("200 - 204".parse(), range(200, 204));
+        assert_eq!("301-301".parse(), range(301, 301));
+        assert!("299-200".parse::<StatusRange>().is_err());
+        assert!("200-".parse::<StatusRange>().is_err());
+    }
+
+    #[test]
+    fn status_range_invalid_codes() {
+        assert!("99".parse::<StatusRange>().is_err());
+        assert!("1000".parse::<StatusRange>().is_err());
+        assert!("ok".parse::<StatusRange>().is_err());
+        assert!("".parse::<StatusRange>().is_err());
+    }
+
+    #[test]
+    fn status_range_contains() {
+        let range: StatusRange = "2xx".parse().unwrap();
+        assert!(range.contains(200) && range.contains(299));
+        assert!(!range.contains(199) && !range.contains(300));
+    }
 }
@@ -1,10 +1,16 @@
 mod commands;
 mod configs;
 mod response_info;
+mod result_file;
+mod run;
+mod stats;
+mod template;
 mod test_result;
 
-use commands::{extract, plot, spam};
+use commands::{compare, extract, plot, spam};
 pub(crate) use response_info::*;
+pub(crate) use result_file::*;
+pub(crate) use run::*;
 pub(crate) use test_result::*;
 
 use std::path::PathBuf;
@@ -20,6 +26,8 @@ enum Command {
     Plot(plot::Options),
     /// extract data from the results
     Extract(extract::Options),
+    /// compare the results of two tests or runs
+    Compare(compare::Options),
 }
 
 #[derive(Parser, Debug)]
@@ -56,5 +64,6 @@ async fn main() -> Result<()> {
         Command::Spam(o) => spam::spam(o, names, config_path, data_dir).await,
         Command::Plot(o) => plot::plot(o, names, data_dir, out_dir.join("graphs")),
         Command::Extract(o) => extract::extract(o, names, data_dir),
+        Command::Compare(o) => compare::compare(o, names, data_dir),
     }
 }
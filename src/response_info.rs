@@ -1,4 +1,8 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use rkyv::{Archive, Deserialize, Serialize};
 use tokio::time::Duration;
@@ -7,7 +11,7 @@ use tokio::time::Duration;
 #[archive(check_bytes)]
 pub enum Status {
     Success,
-    Failure { reason: String },
+    Failure { kind: FailureKind, reason: String },
 }
 
 impl Status {
@@ -17,29 +21,268 @@ impl Status {
             Self::Failure { .. } => false,
         }
     }
+
+    /// why the request failed
+    pub fn failure_kind(&self) -> Option<FailureKind> {
+        match self {
+            Self::Success => None,
+            Self::Failure { kind, .. } => Some(*kind),
+        }
+    }
+}
+
+/// why a request failed, to count failures by cause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub enum FailureKind {
+    /// the request couldn't be built from the test's config
+    Build,
+    Timeout,
+    /// the host couldn't be resolved
+    Dns,
+    ConnectionRefused,
+    Tls,
+    /// opening the connection failed for another reason
+    Connect,
+    /// sending the request or receiving the response failed, eg. the connection was reset
+    Request,
+    /// reading the body failed
+    Body,
+    Decode,
+    Redirect,
+    /// the status code wasn't expected
+    Status,
+    /// the response failed a check, assertion, schema or extraction
+    Check,
+    /// the open model had too many requests in flight to send it
+    Dropped,
+    Other,
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Build => "build",
+            Self::Timeout => "timeout",
+            Self::Dns => "dns",
+            Self::ConnectionRefused => "connection refused",
+            Self::Tls => "tls",
+            Self::Connect => "connect",
+            Self::Request => "request",
+            Self::Body => "body",
+            Self::Decode => "decode",
+            Self::Redirect => "redirect",
+            Self::Status => "status",
+            Self::Check => "check",
+            Self::Dropped => "dropped",
+            Self::Other => "other",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub enum HttpVersion {
+    Http09,
+    Http10,
+    Http11,
+    Http2,
+    Http3,
+}
+
+impl From<reqwest::Version> for HttpVersion {
+    fn from(version: reqwest::Version) -> Self {
+        match version {
+            reqwest::Version::HTTP_09 => Self::Http09,
+            reqwest::Version::HTTP_10 => Self::Http10,
+            reqwest::Version::HTTP_2 => Self::Http2,
+            reqwest::Version::HTTP_3 => Self::Http3,
+            _ => Self::Http11,
+        }
+    }
+}
+
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Http09 => "HTTP/0.9",
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+            Self::Http2 => "HTTP/2",
+            Self::Http3 => "HTTP/3",
+        })
+    }
+}
+
+/// the result of a single step of a scenario
+#[derive(Debug, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct StepInfo {
+    pub name: String,
+    pub time: Duration,
+    pub status_code: Option<u16>,
+}
+
+/// how long each phase of a request took, all but `download` add up to the request's time
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct Timing {
+    /// resolving the host, when a new connection was opened to a host name
+    pub dns: Option<Duration>,
+    /// the TCP connect of a new connection
+    pub connect: Option<Duration>,
+    /// the TLS handshake of a new https connection
+    pub tls: Option<Duration>,
+    /// from sending the request until the response headers arrived
+    pub ttfb: Duration,
+    /// reading the body, when it was read
+    pub download: Option<Duration>,
+    /// whether the connection phases were timed (`phase_timing`), otherwise `ttfb` includes opening
+    /// any new connection
+    pub phased: bool,
+}
+
+impl Timing {
+    /// the names of the `phases`
+    pub const PHASES: [&'static str; 5] = ["dns", "connect", "tls", "ttfb", "download"];
+
+    /// whether the request was sent on a pooled connection, `None` when the phases weren't timed
+    pub fn reused(&self) -> Option<bool> {
+        self.phased.then_some(self.connect.is_none())
+    }
+
+    /// each phase, `None` when it didn't happen
+    pub fn phases(&self) -> [Option<Duration>; 5] {
+        [
+            self.dns,
+            self.connect,
+            self.tls,
+            Some(self.ttfb),
+            self.download,
+        ]
+    }
+}
+
+impl std::ops::AddAssign for Timing {
+    fn add_assign(&mut self, other: Self) {
+        let add = |a: Option<Duration>, b: Option<Duration>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+        };
+        self.dns = add(self.dns, other.dns);
+        self.connect = add(self.connect, other.connect);
+        self.tls = add(self.tls, other.tls);
+        self.ttfb += other.ttfb;
+        self.download = add(self.download, other.download);
+        self.phased |= other.phased;
+    }
+}
+
+impl Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.connect {
+            Some(connect) => {
+                if let Some(dns) = self.dns {
+                    write!(f, "dns {dns:?}, ")?;
+                }
+                write!(f, "connect {connect:?}, ")?;
+                if let Some(tls) = self.tls {
+                    write!(f, "tls {tls:?}, ")?;
+                }
+            }
+            None if self.phased => write!(f, "reused connection, ")?,
+            None => {}
+        }
+        write!(f, "ttfb {:?}", self.ttfb)?;
+        if let Some(download) = self.download {
+            write!(f, ", download {download:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// a part of the response body that doesn't match the test's JSON Schema
+#[derive(Debug, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value
+    pub pointer: String,
+    pub message: String,
+}
+
+impl SchemaViolation {
+    /// the pointer, naming the root of the body which has an empty pointer
+    pub fn location(&self) -> &str {
+        match self.pointer.as_str() {
+            "" => "(root)",
+            pointer => pointer,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Archive)]
 #[archive(check_bytes)]
 pub struct ResponseInfo {
+    /// until the response headers arrived, `total_time` includes reading the body
     pub time: Duration,
+    /// when the request was sent, as an offset from the start of the test
+    pub start_offset: Duration,
+    /// wall-clock time the request was sent, since the unix epoch
+    pub started_at: Duration,
     pub status: Status,
+    /// http status code, if a response was received
+    pub status_code: Option<u16>,
+    /// negotiated http version, if a response was received
+    pub http_version: Option<HttpVersion>,
+    /// size of the body from the `content-length` header
+    pub content_length: Option<u64>,
+    /// number of body bytes read, only known when the body was read, responses aren't decompressed
+    /// so this is the size as sent even for an encoded body
+    pub body_size: Option<u64>,
     pub server_latency: Option<Duration>,
     pub collected: HashMap<String, String>,
+    /// the request rate the test was targeting when this request was sent
+    pub target_rps: Option<f64>,
+    /// when the request was meant to be sent, as an offset from the start of the test
+    pub intended_start: Option<Duration>,
+    /// how long after `intended_start` the request was actually sent
+    pub send_delay: Option<Duration>,
+    /// the name of the request in a weighted mix
+    pub request_name: Option<String>,
+    /// each step of a scenario, `time` is then the total of the steps
+    pub steps: Vec<StepInfo>,
+    /// where the body didn't match the test's JSON Schema
+    pub violations: Vec<SchemaViolation>,
+    /// the phases of the request, if a response was received
+    pub timing: Option<Timing>,
 }
 
 impl ResponseInfo {
     pub fn error(
         time: Duration,
+        kind: FailureKind,
         reason: String,
         server_latency: Option<Duration>,
         collected: Option<HashMap<String, String>>,
     ) -> Self {
         Self {
             time,
-            status: Status::Failure { reason },
+            start_offset: Duration::ZERO,
+            started_at: Duration::ZERO,
+            status: Status::Failure { kind, reason },
+            status_code: None,
+            http_version: None,
+            content_length: None,
+            body_size: None,
             server_latency,
             collected: collected.unwrap_or_default(),
+            target_rps: None,
+            intended_start: None,
+            send_delay: None,
+            request_name: None,
+            steps: vec![],
+            violations: vec![],
+            timing: None,
         }
     }
     pub fn success(
@@ -49,29 +292,114 @@ impl ResponseInfo {
     ) -> Self {
         Self {
             time,
+            start_offset: Duration::ZERO,
+            started_at: Duration::ZERO,
             status: Status::Success,
+            status_code: None,
+            http_version: None,
+            content_length: None,
+            body_size: None,
             server_latency,
             collected,
+            target_rps: None,
+            intended_start: None,
+            send_delay: None,
+            request_name: None,
+            steps: vec![],
+            violations: vec![],
+            timing: None,
         }
     }
+
+    /// latency including reading the body, when the body was read
+    pub fn total_time(&self) -> Option<Duration> {
+        Some(self.time + self.timing?.download?)
+    }
+
+    /// latency measured from when the request was meant to be sent, corrects for coordinated omission
+    pub fn corrected_time(&self) -> Duration {
+        self.time + self.send_delay.unwrap_or_default()
+    }
+
+    pub fn started_at(&self) -> SystemTime {
+        UNIX_EPOCH + self.started_at
+    }
+
+    /// mark the response as failed
+    pub fn fail(&mut self, kind: FailureKind, reason: String) {
+        self.status = Status::Failure { kind, reason };
+    }
 }
 
 impl Display for ResponseInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut s = f.debug_struct("Response");
 
-        if let Status::Failure { reason } = &self.status {
-            s.field("failure", reason);
+        if let Some(name) = &self.request_name {
+            s.field("request", name);
         }
 
-        match &self.server_latency {
-            Some(server_latency) => s.field(
+        if let Status::Failure { kind, reason } = &self.status {
+            s.field("failure", &format!("{kind}: {reason}"));
+        }
+
+        match (&self.status_code, &self.http_version) {
+            (Some(code), Some(version)) => s.field("status", &format!("{code} ({version})")),
+            (Some(code), None) => s.field("status", code),
+            _ => &mut s,
+        };
+
+        match (&self.server_latency, self.total_time()) {
+            (Some(server_latency), Some(total)) => s.field(
+                "time",
+                &format!(
+                    "{:?} (server: {:?}, with body: {total:?})",
+                    self.time, server_latency
+                ),
+            ),
+            (Some(server_latency), None) => s.field(
                 "time",
                 &format!("{:?} (server: {:?})", self.time, server_latency),
             ),
-            None => s.field("time", &self.time),
+            (None, Some(total)) => {
+                s.field("time", &format!("{:?} (with body: {total:?})", self.time))
+            }
+            (None, None) => s.field("time", &self.time),
+        };
+
+        if let Some(timing) = &self.timing {
+            s.field("timing", &timing.to_string());
+        }
+
+        match (&self.body_size, &self.content_length) {
+            (Some(body), Some(length)) if body != length => {
+                s.field("size", &format!("{body} bytes (content-length: {length})"))
+            }
+            (Some(size), _) | (None, Some(size)) => s.field("size", &format!("{size} bytes")),
+            (None, None) => &mut s,
         };
 
+        s.field(
+            "started",
+            &format!(
+                "{} (+{:?})",
+                humantime::format_rfc3339_millis(self.started_at()),
+                self.start_offset
+            ),
+        );
+
+        if !self.steps.is_empty() {
+            let steps: Vec<_> = self
+                .steps
+                .iter()
+                .map(|step| match step.status_code {
+                    Some(code) => format!("{} {code} in {:?}", step.name, step.time),
+                    None => format!("{} in {:?}", step.name, step.time),
+                })
+                .collect();
+            s.field("steps", &steps);
+        }
+
         s.field("collected", &self.collected);
 
         s.finish()
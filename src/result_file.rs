@@ -0,0 +1,248 @@
+use crate::{ResponseInfo, TestResult};
+use anyhow::{anyhow, bail, Result};
+use rkyv::{check_archived_root, to_bytes, AlignedVec, Archive, Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+use tokio::time::Duration;
+
+/// extension of files results are streamed to
+pub const CHUNKS_EXTENSION: &str = "chunks";
+
+/// starts every `.chunks` file, followed by the little endian `u32` format version
+const MAGIC: &[u8; 8] = b"spamcan\0";
+/// bump whenever an archived type changes so older files are rejected instead of misread
+const FORMAT_VERSION: u32 = 2;
+
+/// number of responses buffered before a chunk is written
+const CHUNK_SIZE: usize = 1000;
+/// longest responses are buffered before a chunk is written
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// a frame in a `.chunks` file, stored as a little endian `u64` length followed by the archived chunk
+#[derive(Debug, Serialize, Deserialize, Archive)]
+#[archive(check_bytes)]
+pub enum Chunk {
+    Responses(Vec<ResponseInfo>),
+    /// written once the test completes
+    End {
+        total_time: Duration,
+    },
+}
+
+/// appends responses to `<name>.chunks` while a test runs so results survive a crash
+pub struct ResultWriter {
+    writer: BufWriter<File>,
+    buffer: Vec<ResponseInfo>,
+    last_flush: Instant,
+}
+
+impl ResultWriter {
+    pub fn create<P: AsRef<Path>>(folder: P, name: &str) -> Result<Self> {
+        let _ = fs::create_dir_all(&folder);
+        let file_path = folder.as_ref().join(format!("{name}.{CHUNKS_EXTENSION}"));
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn push(&mut self, response: ResponseInfo) -> Result<()> {
+        self.buffer.push(response);
+        if self.buffer.len() >= CHUNK_SIZE || self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self, total_time: Duration) -> Result<()> {
+        self.flush()?;
+        self.write_chunk(&Chunk::End { total_time })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.last_flush = Instant::now();
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Chunk::Responses(std::mem::take(&mut self.buffer));
+        self.write_chunk(&chunk)?;
+        if let Chunk::Responses(mut buffer) = chunk {
+            buffer.clear();
+            self.buffer = buffer;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        let bytes = to_bytes::<Chunk, 4096>(chunk)?;
+        self.writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// read the results from a `.chunks` file, stopping at the first incomplete or invalid chunk, files
+/// written in another format are an error
+pub fn read_chunks<P: AsRef<Path>>(path: P) -> Result<TestResult> {
+    let path = path.as_ref();
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("invalid results file name {}", path.display()))?
+        .to_owned();
+    let file = fs::read(path)?;
+
+    let Some((version, mut remaining)) = file
+        .strip_prefix(MAGIC.as_slice())
+        .and_then(|rest| rest.split_first_chunk::<4>())
+    else {
+        bail!("not a results file or written by an older version");
+    };
+    let version = u32::from_le_bytes(*version);
+    if version != FORMAT_VERSION {
+        bail!("written in results format {version}, expected format {FORMAT_VERSION}");
+    }
+
+    let mut responses = vec![];
+    let mut total_time = None;
+    while let Some((length, rest)) = remaining.split_first_chunk::<8>() {
+        let length = u64::from_le_bytes(*length) as usize;
+        let Some((payload, rest)) = rest.split_at_checked(length) else {
+            break;
+        };
+        remaining = rest;
+
+        // archives must be aligned, which a slice of the file isn't
+        let mut aligned = AlignedVec::with_capacity(payload.len());
+        aligned.extend_from_slice(payload);
+        let Ok(archived) = check_archived_root::<Chunk>(&aligned) else {
+            eprintln!(
+                "warning: {} has an invalid chunk, ignoring the rest of it",
+                path.display()
+            );
+            break;
+        };
+        match archived.deserialize(&mut rkyv::Infallible)? {
+            Chunk::Responses(chunk) => responses.extend(chunk),
+            Chunk::End { total_time: t } => total_time = Some(t),
+        }
+    }
+
+    // a test that didn't finish ran at least until its last response
+    let total_time = total_time.unwrap_or_else(|| {
+        responses
+            .iter()
+            .map(|r| r.start_offset + r.time)
+            .max()
+            .unwrap_or_default()
+    });
+
+    Ok(TestResult::new(responses, name, total_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn response(start_offset: u64, time: u64) -> ResponseInfo {
+        let mut response = ResponseInfo::success(Duration::from_millis(time), None, HashMap::new());
+        response.start_offset = Duration::from_millis(start_offset);
+        response
+    }
+
+    /// a file with a chunk for each of the responses, ended when `total_time` is given
+    fn write(name: &str, responses: Vec<ResponseInfo>, total_time: Option<u64>) -> Vec<u8> {
+        let folder = std::env::temp_dir().join(format!("spam-can-{}", std::process::id()));
+        let mut writer = ResultWriter::create(&folder, name).unwrap();
+        for response in responses {
+            writer.buffer.push(response);
+            writer.flush().unwrap();
+        }
+        if let Some(total_time) = total_time {
+            writer.finish(Duration::from_millis(total_time)).unwrap();
+        }
+        let path = folder.join(format!("{name}.{CHUNKS_EXTENSION}"));
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    fn read(name: &str, bytes: &[u8]) -> Result<TestResult> {
+        let folder = std::env::temp_dir().join(format!("spam-can-{}", std::process::id()));
+        let path = folder.join(format!("{name}.{CHUNKS_EXTENSION}"));
+        fs::write(&path, bytes).unwrap();
+        let result = read_chunks(&path);
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    #[test]
+    fn reads_what_was_written() {
+        let bytes = write(
+            "whole",
+            vec![response(0, 100), response(50, 100)],
+            Some(400),
+        );
+        let result = read("whole", &bytes).unwrap();
+        assert_eq!(result.name, "whole");
+        assert_eq!(result.responses.len(), 2);
+        assert_eq!(result.total_time, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn truncated_last_chunk_is_ignored() {
+        let bytes = write("truncated", vec![response(0, 100), response(50, 100)], None);
+        let result = read("truncated", &bytes[..bytes.len() - 3]).unwrap();
+        assert_eq!(result.responses.len(), 1);
+        assert_eq!(result.total_time, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn corrupt_length_stops_reading() {
+        let mut bytes = write("length", vec![response(0, 100)], None);
+        let valid = bytes.len();
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0; 64]);
+        assert_eq!(read("length", &bytes).unwrap().responses.len(), 1);
+
+        // a length that fits, around a payload that isn't a chunk
+        bytes.truncate(valid);
+        bytes.extend_from_slice(&16u64.to_le_bytes());
+        bytes.extend_from_slice(&[0xff; 16]);
+        assert_eq!(read("length", &bytes).unwrap().responses.len(), 1);
+    }
+
+    #[test]
+    fn missing_end_uses_the_last_response() {
+        let bytes = write(
+            "unfinished",
+            vec![response(1000, 200), response(2000, 300)],
+            None,
+        );
+        let result = read("unfinished", &bytes).unwrap();
+        assert_eq!(result.responses.len(), 2);
+        assert_eq!(result.total_time, Duration::from_millis(2300));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = write("version", vec![response(0, 100)], Some(100));
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&1u32.to_le_bytes());
+        let error = read("version", &bytes).unwrap_err().to_string();
+        assert!(error.contains("format 1"), "{error}");
+
+        let error = read("version", &bytes[MAGIC.len()..])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not a results file"), "{error}");
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+const MANIFEST: &str = "manifest.json";
+
+/// metadata saved alongside the results of each run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    pub label: Option<String>,
+    pub started_at: String,
+    pub config_path: PathBuf,
+    /// names of the tests saved in this run
+    pub tests: Vec<String>,
+}
+
+/// a directory of results from a single `spam` invocation, stored in `<data_dir>/<id>`
+pub struct Run {
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Run {
+    pub fn create<P: AsRef<Path>>(
+        data_dir: P,
+        label: Option<String>,
+        config_path: PathBuf,
+    ) -> Result<Self> {
+        let now = SystemTime::now();
+        let timestamp = humantime::format_rfc3339_millis(now)
+            .to_string()
+            .replace([':', '.'], "-");
+        let label = label.map(|l| {
+            l.chars()
+                .map(|c| match c.is_ascii_alphanumeric() || c == '_' {
+                    true => c,
+                    false => '-',
+                })
+                .collect::<String>()
+        });
+        let id = match &label {
+            Some(label) => format!("{timestamp}-{label}"),
+            None => timestamp,
+        };
+
+        let run = Self {
+            dir: data_dir.as_ref().join(&id),
+            manifest: Manifest {
+                id,
+                label,
+                started_at: humantime::format_rfc3339_seconds(now).to_string(),
+                config_path,
+                tests: vec![],
+            },
+        };
+        fs::create_dir_all(&run.dir)?;
+        run.save_manifest()?;
+        Ok(run)
+    }
+
+    /// record a test as saved in this run
+    pub fn add_test(&mut self, name: String) -> Result<()> {
+        self.manifest.tests.push(name);
+        self.save_manifest()
+    }
+
+    fn save_manifest(&self) -> Result<()> {
+        let manifest = serde_json::to_string_pretty(&self.manifest)?;
+        Ok(fs::write(self.dir.join(MANIFEST), manifest)?)
+    }
+
+    /// all runs in the data directory, oldest first
+    pub fn list<P: AsRef<Path>>(data_dir: P) -> Result<Vec<Run>> {
+        let mut runs: Vec<_> = fs::read_dir(data_dir)?
+            .filter_map(|d| d.ok())
+            .filter_map(|d| {
+                let manifest = fs::read_to_string(d.path().join(MANIFEST)).ok()?;
+                let manifest = serde_json::from_str(&manifest).ok()?;
+                Some(Run {
+                    dir: d.path(),
+                    manifest,
+                })
+            })
+            .collect();
+        runs.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+        Ok(runs)
+    }
+
+    /// find a run by its id, its label (the latest with that label) or `latest`
+    pub fn find<P: AsRef<Path>>(data_dir: P, selector: &str) -> Option<Run> {
+        let mut runs = Run::list(data_dir).ok()?;
+        if selector == "latest" {
+            return runs.pop();
+        }
+
+        let position = runs
+            .iter()
+            .rposition(|r| r.manifest.id == selector)
+            .or_else(|| {
+                runs.iter()
+                    .rposition(|r| r.manifest.label.as_deref() == Some(selector))
+            })?;
+        Some(runs.swap_remove(position))
+    }
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct RunSelection {
+    /// run to load results from: a run id, a run label (latest with that label) or `latest`
+    #[arg(long, default_value = "latest")]
+    run: String,
+}
+
+impl RunSelection {
+    /// the directory holding the selected run's results
+    pub fn dir<P: AsRef<Path>>(&self, data_dir: P) -> Result<PathBuf> {
+        Run::find(data_dir, &self.run)
+            .map(|r| r.dir)
+            .ok_or_else(|| anyhow!("no run found matching '{}'", self.run))
+    }
+}
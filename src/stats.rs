@@ -0,0 +1,246 @@
+use rand::Rng;
+
+/// the index of the value at `ratio` through `len` sorted values, the top percentile is the last
+pub fn percentile_index(len: usize, ratio: f64) -> Option<usize> {
+    Some(((len as f64 * ratio).floor() as usize).min(len.checked_sub(1)?))
+}
+
+/// the value at `ratio` through already sorted data
+pub fn percentile<T: Copy>(sorted: &[T], ratio: f64) -> Option<T> {
+    sorted.get(percentile_index(sorted.len(), ratio)?).copied()
+}
+
+pub struct MannWhitney {
+    /// the U statistic for the first sample
+    pub u: f64,
+    pub z: f64,
+    /// two sided p-value from the normal approximation
+    pub p: f64,
+}
+
+/// Mann-Whitney U test of whether values in `b` tend to differ from those in `a`, with tie correction
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<MannWhitney> {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let mut all: Vec<_> = a
+        .iter()
+        .map(|v| (*v, true))
+        .chain(b.iter().map(|v| (*v, false)))
+        .collect();
+    all.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut rank_sum_a = 0f64;
+    let mut tie_term = 0f64;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        // ranks are 1 based, ties share the average rank
+        let rank = (i + j) as f64 / 2f64 + 1f64;
+        let ties = (j - i + 1) as f64;
+        rank_sum_a += rank * all[i..=j].iter().filter(|(_, is_a)| *is_a).count() as f64;
+        tie_term += ties * ties * ties - ties;
+        i = j + 1;
+    }
+
+    let n = n1 + n2;
+    let u = rank_sum_a - n1 * (n1 + 1f64) / 2f64;
+    let mean = n1 * n2 / 2f64;
+    let variance = n1 * n2 / 12f64 * ((n + 1f64) - tie_term / (n * (n - 1f64)));
+    if variance <= 0f64 {
+        return Some(MannWhitney {
+            u,
+            z: 0f64,
+            p: 1f64,
+        });
+    }
+    // continuity correction
+    let z = match u - mean {
+        d if d > 0f64 => (d - 0.5) / variance.sqrt(),
+        d if d < 0f64 => (d + 0.5) / variance.sqrt(),
+        _ => 0f64,
+    };
+    let p = (2f64 * (1f64 - normal_cdf(z.abs()))).clamp(0f64, 1f64);
+
+    Some(MannWhitney { u, z, p })
+}
+
+/// bootstrap confidence intervals for the difference (`b - a`) of each percentile in `ratios`
+pub fn bootstrap_percentile_diff(
+    a_sorted: &[f64],
+    b_sorted: &[f64],
+    ratios: &[f64],
+    iterations: usize,
+    confidence: f64,
+) -> Option<Vec<(f64, f64)>> {
+    if a_sorted.is_empty() || b_sorted.is_empty() || iterations == 0 {
+        return None;
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut diffs = vec![Vec::with_capacity(iterations); ratios.len()];
+    let mut a_indices = vec![0usize; a_sorted.len()];
+    let mut b_indices = vec![0usize; b_sorted.len()];
+    for _ in 0..iterations {
+        // the data is sorted so the resample's order statistics are at its sorted indices
+        a_indices.fill_with(|| rng.gen_range(0..a_sorted.len()));
+        b_indices.fill_with(|| rng.gen_range(0..b_sorted.len()));
+        for (ratio, diffs) in ratios.iter().zip(diffs.iter_mut()) {
+            let a = select(&mut a_indices, *ratio);
+            let b = select(&mut b_indices, *ratio);
+            diffs.push(b_sorted[b] - a_sorted[a]);
+        }
+    }
+
+    let tail = (1f64 - confidence) / 2f64;
+    Some(
+        diffs
+            .into_iter()
+            .map(|mut d| {
+                d.sort_unstable_by(f64::total_cmp);
+                (
+                    percentile(&d, tail).unwrap_or_default(),
+                    percentile(&d, 1f64 - tail).unwrap_or_default(),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn select(indices: &mut [usize], ratio: f64) -> usize {
+    let n = percentile_index(indices.len(), ratio).expect("resamples aren't empty");
+    *indices.select_nth_unstable(n).1
+}
+
+/// two sided p-value for the difference between two success rates
+pub fn two_proportion_p(
+    successes_a: usize,
+    n_a: usize,
+    successes_b: usize,
+    n_b: usize,
+) -> Option<f64> {
+    if n_a == 0 || n_b == 0 {
+        return None;
+    }
+    let (n_a, n_b) = (n_a as f64, n_b as f64);
+    let (p_a, p_b) = (successes_a as f64 / n_a, successes_b as f64 / n_b);
+    let pooled = (successes_a + successes_b) as f64 / (n_a + n_b);
+    let se = (pooled * (1f64 - pooled) * (1f64 / n_a + 1f64 / n_b)).sqrt();
+    if se == 0f64 {
+        return Some(1f64);
+    }
+    let z = (p_b - p_a) / se;
+    Some((2f64 * (1f64 - normal_cdf(z.abs()))).clamp(0f64, 1f64))
+}
+
+pub fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / std::f64::consts::SQRT_2)
+}
+
+/// complementary error function, fractional error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1f64 / (1f64 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    match x >= 0f64 {
+        true => r,
+        false => 2f64 - r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-4
+    }
+
+    #[test]
+    fn percentile_of_sorted_data() {
+        let data: Vec<_> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&data, 0.0), Some(1.0));
+        assert_eq!(percentile(&data, 0.5), Some(6.0));
+        assert_eq!(percentile(&data, 0.95), Some(10.0));
+        // the top percentile is the largest value
+        assert_eq!(percentile(&data, 1.0), Some(10.0));
+        assert_eq!(percentile::<f64>(&[], 0.5), None);
+        assert_eq!(percentile_index(0, 0.5), None);
+        assert_eq!(percentile_index(4, 0.75), Some(3));
+    }
+
+    #[test]
+    fn mann_whitney_separated_samples() {
+        // R: wilcox.test(1:3, 4:6, exact = FALSE) gives W = 0, p = 0.08086
+        let mw = mann_whitney_u(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(mw.u, 0.0);
+        assert!(close(mw.z, -1.745743), "z = {}", mw.z);
+        assert!(close(mw.p, 0.080856), "p = {}", mw.p);
+    }
+
+    #[test]
+    fn mann_whitney_with_ties() {
+        // R: wilcox.test(c(1, 2, 2, 5), c(2, 3, 4, 6, 7), exact = FALSE) gives W = 4, p = 0.1706
+        let mw = mann_whitney_u(&[1.0, 2.0, 2.0, 5.0], &[2.0, 3.0, 4.0, 6.0, 7.0]).unwrap();
+        assert_eq!(mw.u, 4.0);
+        assert!(close(mw.z, -1.370250), "z = {}", mw.z);
+        assert!(close(mw.p, 0.170609), "p = {}", mw.p);
+    }
+
+    #[test]
+    fn mann_whitney_identical_samples() {
+        let mw = mann_whitney_u(&[3.0, 3.0], &[3.0, 3.0]).unwrap();
+        assert_eq!(mw.p, 1.0);
+        assert!(mann_whitney_u(&[], &[1.0]).is_none());
+    }
+
+    #[test]
+    fn bootstrap_constant_samples() {
+        // every resample of constant data has the same percentiles
+        let intervals =
+            bootstrap_percentile_diff(&[1.0; 20], &[3.0; 30], &[0.5, 0.99], 100, 0.95).unwrap();
+        assert_eq!(intervals, vec![(2.0, 2.0), (2.0, 2.0)]);
+        assert!(bootstrap_percentile_diff(&[1.0], &[1.0], &[0.5], 0, 0.95).is_none());
+        assert!(bootstrap_percentile_diff(&[], &[1.0], &[0.5], 100, 0.95).is_none());
+    }
+
+    #[test]
+    fn bootstrap_interval_contains_shift() {
+        let a: Vec<_> = (0..200).map(f64::from).collect();
+        let b: Vec<_> = a.iter().map(|v| v + 50.0).collect();
+        let intervals = bootstrap_percentile_diff(&a, &b, &[0.5], 1000, 0.95).unwrap();
+        let (low, high) = intervals[0];
+        assert!(low <= 50.0 && 50.0 <= high, "[{low}, {high}]");
+        assert!(low > 0.0, "[{low}, {high}]");
+    }
+
+    #[test]
+    fn two_proportions() {
+        // pooled z test: z = 2.123977
+        let p = two_proportion_p(45, 100, 60, 100).unwrap();
+        assert!(close(p, 0.033672), "p = {p}");
+        assert_eq!(two_proportion_p(100, 100, 100, 100), Some(1.0));
+        assert_eq!(two_proportion_p(0, 0, 1, 1), None);
+    }
+
+    #[test]
+    fn normal_cdf_known_values() {
+        assert!(close(normal_cdf(0.0), 0.5));
+        assert!(close(normal_cdf(1.959964), 0.975));
+        assert!(close(normal_cdf(-1.0), 0.158655));
+    }
+}
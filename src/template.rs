@@ -0,0 +1,248 @@
+use std::{collections::HashMap, fmt::Display, time::SystemTime};
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+/// a string containing `{{...}}` placeholders that are evaluated for each request:
+///
+/// - `{{uuid}}` - a random uuid, `{{uuid simple}}` for one without hyphens
+/// - `{{random_int 1 100}}` - a random integer in an inclusive range
+/// - `{{choice [en-us,en-gb]}}` - a random item from a list
+/// - `{{seq}}` - the index of the request in the test, starting at 0
+/// - `{{now_iso}}` - the current time in rfc3339 format
+/// - `{{name}}` - any other name is a variable supplied by the test's feeder
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template(Vec<Segment>);
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Uuid { simple: bool },
+    RandomInt { min: i64, max: i64 },
+    Choice(Vec<String>),
+    Seq,
+    NowIso,
+    Variable(String),
+}
+
+/// per request values used when rendering templates
+pub struct TemplateContext<'a> {
+    pub seq: usize,
+    /// variables from the feeder row for this request
+    pub vars: Option<&'a HashMap<String, String>>,
+}
+
+impl Template {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut rest = value;
+        while let Some(open) = rest.find("{{") {
+            let close = rest[open..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed placeholder in '{value}'"))?;
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_owned()));
+            }
+            segments.push(Segment::parse(&rest[open + 2..open + close])?);
+            rest = &rest[open + close + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_owned()));
+        }
+        Ok(Self(segments))
+    }
+
+    /// a template that renders `value` as is, without parsing placeholders
+    pub fn literal(value: impl Into<String>) -> Self {
+        Self(vec![Segment::Literal(value.into())])
+    }
+
+    /// whether the template renders the same value for every request
+    pub fn is_literal(&self) -> bool {
+        self.0.iter().all(|s| matches!(s, Segment::Literal(_)))
+    }
+
+    /// names of the variables used by the template
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|s| match s {
+            Segment::Variable(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> String {
+        let mut rendered = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(value) => rendered.push_str(value),
+                Segment::Uuid { simple: true } => {
+                    rendered += &uuid::Uuid::new_v4().simple().to_string()
+                }
+                Segment::Uuid { simple: false } => rendered += &uuid::Uuid::new_v4().to_string(),
+                Segment::RandomInt { min, max } => {
+                    rendered += &rand::thread_rng().gen_range(*min..=*max).to_string()
+                }
+                Segment::Choice(items) => {
+                    rendered.push_str(items.choose(&mut rand::thread_rng()).map_or("", |v| v))
+                }
+                Segment::Seq => rendered += &context.seq.to_string(),
+                Segment::NowIso => {
+                    rendered += &humantime::format_rfc3339_millis(SystemTime::now()).to_string()
+                }
+                Segment::Variable(name) => {
+                    rendered.push_str(context.vars.and_then(|v| v.get(name)).map_or("", |v| v))
+                }
+            }
+        }
+        rendered
+    }
+}
+
+impl Segment {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim();
+        let (name, args) = expression
+            .split_once(char::is_whitespace)
+            .map_or((expression, ""), |(n, a)| (n, a.trim()));
+
+        match (name, args) {
+            ("uuid", "") => Ok(Self::Uuid { simple: false }),
+            ("uuid", "simple") => Ok(Self::Uuid { simple: true }),
+            ("random_int", args) => {
+                let bounds: Vec<_> = args
+                    .split_whitespace()
+                    .map(|v| v.parse::<i64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("invalid bound in '{{{{{expression}}}}}': {e}"))?;
+                match bounds[..] {
+                    [min, max] if min <= max => Ok(Self::RandomInt { min, max }),
+                    _ => Err(format!(
+                        "'{{{{{expression}}}}}' expects a minimum and maximum, eg. `random_int 1 100`"
+                    )),
+                }
+            }
+            ("choice", args) => {
+                let items = args.trim_start_matches('[').trim_end_matches(']');
+                let items: Vec<_> = items.split(',').map(|i| i.trim().to_owned()).collect();
+                match items.iter().all(|i| i.is_empty()) {
+                    true => Err(format!(
+                        "'{{{{{expression}}}}}' has no items to choose from"
+                    )),
+                    false => Ok(Self::Choice(items)),
+                }
+            }
+            ("seq", "") => Ok(Self::Seq),
+            ("now_iso", "") => Ok(Self::NowIso),
+            (name, "")
+                if !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) =>
+            {
+                Ok(Self::Variable(name.to_owned()))
+            }
+            _ => Err(format!("unknown placeholder '{{{{{expression}}}}}'")),
+        }
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(value) => f.write_str(value)?,
+                Segment::Uuid { simple: true } => f.write_str("{{uuid simple}}")?,
+                Segment::Uuid { simple: false } => f.write_str("{{uuid}}")?,
+                Segment::RandomInt { min, max } => write!(f, "{{{{random_int {min} {max}}}}}")?,
+                Segment::Choice(items) => write!(f, "{{{{choice [{}]}}}}", items.join(","))?,
+                Segment::Seq => f.write_str("{{seq}}")?,
+                Segment::NowIso => f.write_str("{{now_iso}}")?,
+                Segment::Variable(name) => write!(f, "{{{{{name}}}}}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, seq: usize, vars: &[(&str, &str)]) -> String {
+        let vars: HashMap<_, _> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let context = TemplateContext {
+            seq,
+            vars: Some(&vars),
+        };
+        Template::parse(template).unwrap().render(&context)
+    }
+
+    #[test]
+    fn parse_literal() {
+        let template = Template::parse("https://example.com/a?b=c").unwrap();
+        assert!(template.is_literal());
+        assert_eq!(template.to_string(), "https://example.com/a?b=c");
+        assert!(Template::parse("").unwrap().is_literal());
+    }
+
+    #[test]
+    fn parse_placeholders() {
+        let source = "/{{seq}}/{{uuid}}/{{uuid simple}}?n={{random_int -5 10}}&l={{choice [en-us,en-gb]}}&t={{now_iso}}&u={{user_id}}";
+        let template = Template::parse(source).unwrap();
+        assert!(!template.is_literal());
+        assert_eq!(template.to_string(), source);
+        assert_eq!(template.variables().collect::<Vec<_>>(), ["user_id"]);
+    }
+
+    #[test]
+    fn parse_trims_whitespace() {
+        let template = Template::parse("{{ random_int  1   2 }}-{{ name }}").unwrap();
+        assert_eq!(template.to_string(), "{{random_int 1 2}}-{{name}}");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for invalid in [
+            "{{seq",
+            "a{{}}b",
+            "{{random_int 5 1}}",
+            "{{random_int 1}}",
+            "{{random_int a b}}",
+            "{{choice []}}",
+            "{{uuid v7}}",
+            "{{seq 1}}",
+            "{{user id}}",
+        ] {
+            assert!(Template::parse(invalid).is_err(), "{invalid} parsed");
+        }
+    }
+
+    #[test]
+    fn render_values() {
+        assert_eq!(
+            render("/users/{{user_id}}/{{seq}}", 7, &[("user_id", "42")]),
+            "/users/42/7"
+        );
+        // a variable the row doesn't have renders empty
+        assert_eq!(render("a{{missing}}b", 0, &[]), "ab");
+
+        let n: i64 = render("{{random_int -2 2}}", 0, &[]).parse().unwrap();
+        assert!((-2..=2).contains(&n));
+        assert!(["x", "y"].contains(&render("{{choice [x, y]}}", 0, &[]).as_str()));
+        assert_eq!(render("{{uuid}}", 0, &[]).len(), 36);
+        assert_eq!(render("{{uuid simple}}", 0, &[]).len(), 32);
+        assert!(humantime::parse_rfc3339(&render("{{now_iso}}", 0, &[])).is_ok());
+    }
+}
@@ -1,11 +1,14 @@
-use crate::ResponseInfo;
+use crate::{read_chunks, stats, ResponseInfo, CHUNKS_EXTENSION};
 use anyhow::Result;
-use rkyv::{check_archived_root, to_bytes, Archive, Deserialize, Serialize};
+use ndhistogram::{
+    axis::{BinInterval, Variable},
+    ndhistogram, AxesTuple, Histogram, VecHistogram,
+};
+use rkyv::{Archive, Deserialize, Serialize};
 use std::{
     ffi::OsStr,
-    fs::{self, DirEntry, File},
-    io::{BufWriter, Write},
-    path::{Path, PathBuf},
+    fs::{self, DirEntry},
+    path::Path,
 };
 use tokio::time::Duration;
 
@@ -13,7 +16,7 @@ use tokio::time::Duration;
 #[archive(check_bytes)]
 pub struct TestResult {
     pub name: String,
-    total_time: Duration,
+    pub total_time: Duration,
     request_time: Duration,
     pub responses: Vec<ResponseInfo>,
     success_count: usize,
@@ -42,59 +45,53 @@ impl TestResult {
         self.responses.iter().filter(|r| !r.status.is_success())
     }
 
-    fn success_total_time(&self) -> Duration {
-        self.success_responses().map(|r| r.time).sum()
-    }
-
-    pub fn failure_total_time(&self) -> Duration {
-        self.failure_responses().map(|r| r.time).sum()
-    }
-
-    pub fn avg_success(&self) -> Option<Duration> {
-        self.success_total_time()
-            .checked_div(self.success_count as u32)
-    }
-
-    pub fn avg_failure(&self) -> Option<Duration> {
-        self.failure_total_time()
-            .checked_div(self.failure_count as u32)
+    /// the names of the requests in a weighted mix, sorted
+    pub fn request_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self
+            .responses
+            .iter()
+            .filter_map(|r| r.request_name.as_deref())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
     }
 
-    pub fn report(&self) -> String {
-        format!(
-            "{}:
-    time: {:?} (~{} rps)
-    success: {} ({:?} avg)
-    failure: {} ({:?} avg)
-        ",
-            self.name,
-            self.total_time,
-            ((self.success_count + self.failure_count) as f64 / self.total_time.as_secs_f64())
-                as u64,
-            self.success_count,
-            self.avg_success().unwrap_or(Duration::from_secs(0)),
-            self.failure_count,
-            self.avg_failure().unwrap_or(Duration::from_secs(0))
-        )
+    /// the names of the steps of a scenario, in the order they were sent
+    pub fn step_names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        for step in self.responses.iter().flat_map(|r| &r.steps) {
+            if !names.contains(&step.name.as_str()) {
+                names.push(step.name.as_str());
+            }
+        }
+        names
     }
 
-    fn file_path<P: AsRef<Path>>(&self, folder: P) -> PathBuf {
-        folder.as_ref().join(format!("{}.rkyv", self.name))
+    /// latencies of the named step of a scenario
+    pub fn step_times<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Duration> + 'a {
+        self.responses
+            .iter()
+            .flat_map(|r| &r.steps)
+            .filter(move |s| s.name == name)
+            .map(|s| s.time)
     }
 
-    pub fn save<P: AsRef<Path>>(&self, folder: P) -> Result<()> {
-        let file_path = self.file_path(&folder);
-        let _ = fs::create_dir_all(&folder);
-        let mut writer = BufWriter::new(File::create(file_path)?);
-        let bytes = to_bytes::<Self, 1024>(self)?;
-        Ok(writer.write_all(&bytes)?)
+    /// responses to the named request in a weighted mix
+    pub fn request_responses<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ResponseInfo> {
+        self.responses
+            .iter()
+            .filter(move |r| r.request_name.as_deref() == Some(name))
     }
 
     pub fn load_filtered<P: AsRef<Path>>(
         data_dir: P,
         names: Option<Vec<String>>,
     ) -> Result<impl Iterator<Item = TestResult>> {
-        Ok(Self::rkyv_files(data_dir)?
+        Ok(Self::chunk_files(data_dir)?
             .filter(|file| match &names {
                 Some(names) => file
                     .path()
@@ -106,22 +103,132 @@ impl TestResult {
             })
             .collect::<Vec<_>>()
             .into_iter()
-            .map(Self::unarchive_file)
-            .filter_map(|res| res.ok()))
+            .filter_map(|file| {
+                let path = file.path();
+                read_chunks(&path)
+                    .map_err(|e| eprintln!("warning: skipping {}: {e:#}", path.display()))
+                    .ok()
+            }))
     }
 
-    fn rkyv_files<P: AsRef<Path>>(directory: P) -> Result<impl Iterator<Item = DirEntry>> {
+    fn chunk_files<P: AsRef<Path>>(directory: P) -> Result<impl Iterator<Item = DirEntry>> {
         Ok(fs::read_dir(directory)?
             .filter_map(|f| f.ok())
-            .filter(|file| file.path().extension().and_then(OsStr::to_str) == Some("rkyv")))
+            .filter(|file| {
+                file.path().extension().and_then(OsStr::to_str) == Some(CHUNKS_EXTENSION)
+            }))
+    }
+}
+
+/// totals for a test, kept while its results are streamed to disk
+pub struct TestSummary {
+    pub name: String,
+    total_time: Duration,
+    success_count: usize,
+    failure_count: usize,
+    success_time: Duration,
+    failure_time: Duration,
+    /// latency of every successful request in microseconds, bucketed so memory doesn't grow with the
+    /// test, failures are left out so fast errors can't pull the percentiles down
+    latencies: LatencyHistogram,
+    max_latency: Duration,
+}
+
+type LatencyHistogram = VecHistogram<AxesTuple<(Variable<f64>,)>, u64>;
+
+/// buckets 1% wide from 1µs up to a couple of hours
+fn latency_histogram() -> LatencyHistogram {
+    ndhistogram!(Variable::new((0..2300).map(|i| 1.01f64.powi(i))); u64)
+}
+
+impl TestSummary {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            total_time: Duration::ZERO,
+            success_count: 0,
+            failure_count: 0,
+            success_time: Duration::ZERO,
+            failure_time: Duration::ZERO,
+            latencies: latency_histogram(),
+            max_latency: Duration::ZERO,
+        }
+    }
+
+    pub fn add(&mut self, response: &ResponseInfo) {
+        match response.status.is_success() {
+            true => {
+                self.success_count += 1;
+                self.success_time += response.time;
+                self.latencies.fill(&(response.time.as_secs_f64() * 1e6));
+                self.max_latency = self.max_latency.max(response.time);
+            }
+            false => {
+                self.failure_count += 1;
+                self.failure_time += response.time;
+            }
+        }
+    }
+
+    pub fn finish(&mut self, total_time: Duration) {
+        self.total_time = total_time;
+    }
+
+    /// the latency at `ratio` through the successful requests, rounded up to the edge of its bucket
+    pub fn percentile(&self, ratio: f64) -> Option<Duration> {
+        let rank = stats::percentile_index(self.success_count, ratio)? as u64;
+        let mut seen = 0;
+        let bucket = self.latencies.iter().find(|item| {
+            seen += *item.value;
+            seen > rank
+        })?;
+        let micros = match bucket.bin {
+            BinInterval::Underflow { end } => end,
+            BinInterval::Bin { end, .. } => end,
+            BinInterval::Overflow { .. } => return Some(self.max_latency),
+        };
+        Some(Duration::from_secs_f64(micros / 1e6).min(self.max_latency))
+    }
+
+    pub fn avg_success(&self) -> Option<Duration> {
+        self.success_time.checked_div(self.success_count as u32)
+    }
+
+    pub fn avg_failure(&self) -> Option<Duration> {
+        self.failure_time.checked_div(self.failure_count as u32)
+    }
+
+    /// achieved requests per second over the whole test
+    pub fn rps(&self) -> f64 {
+        (self.success_count + self.failure_count) as f64 / self.total_time.as_secs_f64()
     }
 
-    fn unarchive_file(dir: DirEntry) -> Result<TestResult> {
-        let file = fs::read(dir.path())?;
-        let archived = check_archived_root::<TestResult>(&file).unwrap();
+    /// percentage of requests that succeeded
+    pub fn success_rate(&self) -> Option<f64> {
+        match self.success_count + self.failure_count {
+            0 => None,
+            total => Some(100f64 * self.success_count as f64 / total as f64),
+        }
+    }
 
-        let result: TestResult = archived.deserialize(&mut rkyv::Infallible)?;
+    pub fn total_time(&self) -> Duration {
+        self.total_time
+    }
 
-        Ok(result)
+    pub fn report(&self) -> String {
+        format!(
+            "{}:
+    time: {:?} (~{} rps)
+    success: {} ({:?} avg)
+    failure: {} ({:?} avg)
+        ",
+            self.name,
+            self.total_time,
+            self.rps() as u64,
+            self.success_count,
+            self.avg_success().unwrap_or(Duration::from_secs(0)),
+            self.failure_count,
+            self.avg_failure().unwrap_or(Duration::from_secs(0))
+        )
     }
 }